@@ -0,0 +1,124 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::{MAX_GRID_LIMIT, Point};
+
+
+pub(crate) const USAGE: &str = "usage: pixelpath [--grid COLUMNSxROWS] [--grid-limit COLUMNSxROWS] [--path-warning COUNT] [--path-limit COUNT] [--no-session] [FILE]\n       pixelpath --register | --unregister";
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum FileTypeChange {
+    Register,
+    Unregister,
+}
+
+
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Arguments {
+    pub grid_count: Option<Point>,
+    pub grid_limit: Option<Point>,
+    pub path_warning: Option<usize>,
+    pub path_limit: Option<usize>,
+    pub no_session: bool,
+    pub file: Option<PathBuf>,
+    pub file_type_change: Option<FileTypeChange>,
+}
+
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum ArgumentError {
+    UnknownOption(String),
+    MissingValue(&'static str),
+    InvalidGrid(String),
+    InvalidCount(String),
+    ExtraFile(PathBuf),
+}
+impl fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOption(option) => write!(f, "unknown option {:?}", option),
+            Self::MissingValue(option) => write!(f, "{} requires a value", option),
+            Self::InvalidGrid(value) => write!(
+                f, "{:?} is not a grid size between 1x1 and {}x{}", value, MAX_GRID_LIMIT.x, MAX_GRID_LIMIT.y,
+            ),
+            Self::InvalidCount(value) => write!(f, "{:?} is not a positive number of paths", value),
+            Self::ExtraFile(path) => write!(f, "only one file can be opened, but {} was also given", path.display()),
+        }
+    }
+}
+impl std::error::Error for ArgumentError {}
+
+
+// the arguments without the program name
+pub(crate) fn parse_arguments<I: IntoIterator<Item = OsString>>(args: I) -> Result<Arguments, ArgumentError> {
+    let mut arguments = Arguments::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--grid" {
+            let value = args.next()
+                .ok_or(ArgumentError::MissingValue("--grid"))?;
+            let value = value.to_string_lossy().into_owned();
+            let grid_count = parse_grid(&value)
+                .ok_or(ArgumentError::InvalidGrid(value))?;
+            arguments.grid_count = Some(grid_count);
+        } else if arg == "--grid-limit" {
+            let value = args.next()
+                .ok_or(ArgumentError::MissingValue("--grid-limit"))?;
+            let value = value.to_string_lossy().into_owned();
+            let grid_limit = parse_grid(&value)
+                .ok_or(ArgumentError::InvalidGrid(value))?;
+            arguments.grid_limit = Some(grid_limit);
+        } else if arg == "--path-warning" {
+            let value = args.next()
+                .ok_or(ArgumentError::MissingValue("--path-warning"))?;
+            let value = value.to_string_lossy().into_owned();
+            let path_warning = parse_count(&value)
+                .ok_or(ArgumentError::InvalidCount(value))?;
+            arguments.path_warning = Some(path_warning);
+        } else if arg == "--path-limit" {
+            let value = args.next()
+                .ok_or(ArgumentError::MissingValue("--path-limit"))?;
+            let value = value.to_string_lossy().into_owned();
+            let path_limit = parse_count(&value)
+                .ok_or(ArgumentError::InvalidCount(value))?;
+            arguments.path_limit = Some(path_limit);
+        } else if arg == "--no-session" {
+            arguments.no_session = true;
+        } else if arg == "--register" {
+            arguments.file_type_change = Some(FileTypeChange::Register);
+        } else if arg == "--unregister" {
+            arguments.file_type_change = Some(FileTypeChange::Unregister);
+        } else if arg.to_string_lossy().starts_with("--") {
+            return Err(ArgumentError::UnknownOption(arg.to_string_lossy().into_owned()));
+        } else if arguments.file.is_some() {
+            return Err(ArgumentError::ExtraFile(PathBuf::from(arg)));
+        } else {
+            arguments.file = Some(PathBuf::from(arg));
+        }
+    }
+    Ok(arguments)
+}
+
+
+// a grid size such as `8x8`, within the limits
+fn parse_grid(value: &str) -> Option<Point> {
+    let (columns, rows) = value.split_once(['x', 'X'])?;
+    let grid_count = Point {
+        x: columns.trim().parse().ok()?,
+        y: rows.trim().parse().ok()?,
+    };
+    let in_range = (1..=MAX_GRID_LIMIT.x).contains(&grid_count.x) && (1..=MAX_GRID_LIMIT.y).contains(&grid_count.y);
+    if in_range {
+        Some(grid_count)
+    } else {
+        None
+    }
+}
+
+
+fn parse_count(value: &str) -> Option<usize> {
+    value.trim().parse().ok()
+        .filter(|&count| count > 0)
+}
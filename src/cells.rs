@@ -0,0 +1,81 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::{CanvasConfig, ClosedPath, Point};
+
+
+// in cell units, counted from the grid origin
+fn cell_index(path: &ClosedPath, canvas: CanvasConfig, grid_shift: Point) -> Option<Point> {
+    if !path.cell || path.points.len() != 4 {
+        return None;
+    }
+    Some(Point {
+        x: (path.points[0].x - grid_shift.x).div_euclid(canvas.horizontal_factor),
+        y: (path.points[0].y - grid_shift.y).div_euclid(canvas.vertical_factor),
+    })
+}
+
+
+// 4-connected; a region that already has the color is cleared instead, and the fill never
+// leaves the grid
+pub(crate) fn flood_fill(
+    paths: &mut Vec<ClosedPath>,
+    grid_count: Point,
+    canvas: CanvasConfig,
+    grid_shift: Point,
+    start: Point,
+    color: u32,
+) {
+    // if the grid is shifted, the last cell in each direction would stick out of the canvas
+    let cell_count = Point {
+        x: if grid_shift.x != 0 { grid_count.x - 1 } else { grid_count.x },
+        y: if grid_shift.y != 0 { grid_count.y - 1 } else { grid_count.y },
+    };
+    let in_grid = |cell: Point| {
+        cell.x >= 0 && cell.y >= 0 && cell.x < cell_count.x && cell.y < cell_count.y
+    };
+    if !in_grid(start) {
+        return;
+    }
+
+    let painted: BTreeMap<Point, u32> = paths.iter()
+        .filter_map(|path| cell_index(path, canvas, grid_shift).map(|cell| (cell, path.color)))
+        .collect();
+    let target_color = painted.get(&start).copied();
+
+    // breadth-first search over the cells with the same color
+    let mut region = BTreeSet::new();
+    let mut queue = VecDeque::new();
+    region.insert(start);
+    queue.push_back(start);
+    while let Some(cell) = queue.pop_front() {
+        let neighbors = [
+            Point { x: cell.x - 1, y: cell.y },
+            Point { x: cell.x + 1, y: cell.y },
+            Point { x: cell.x, y: cell.y - 1 },
+            Point { x: cell.x, y: cell.y + 1 },
+        ];
+        for neighbor in neighbors {
+            if !in_grid(neighbor) || painted.get(&neighbor).copied() != target_color {
+                continue;
+            }
+            if region.insert(neighbor) {
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    // clear the region, then paint it unless it already had the color
+    paths.retain(|path| match cell_index(path, canvas, grid_shift) {
+        Some(cell) => !region.contains(&cell),
+        None => true,
+    });
+    if target_color != Some(color) {
+        for cell in region {
+            let top_left = Point {
+                x: grid_shift.x + cell.x * canvas.horizontal_factor,
+                y: grid_shift.y + cell.y * canvas.vertical_factor,
+            };
+            paths.push(ClosedPath::new_cell(top_left, canvas, color));
+        }
+    }
+}
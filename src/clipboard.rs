@@ -0,0 +1,40 @@
+use windows::core::{Error, Result};
+use windows::Win32::Foundation::{GlobalFree, HANDLE, HWND};
+use windows::Win32::System::DataExchange::{CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData};
+use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+use windows::Win32::System::Ole::CF_UNICODETEXT;
+
+
+pub(crate) fn copy_text(owner: HWND, text: &str) -> Result<()> {
+    let text_wide: Vec<u16> = text.encode_utf16()
+        .chain(std::iter::once(0x0000))
+        .collect();
+
+    unsafe { OpenClipboard(owner) }?;
+    let result = fill_clipboard(&text_wide);
+    // close the clipboard whether or not filling it worked
+    let _ = unsafe { CloseClipboard() };
+    result
+}
+
+
+fn fill_clipboard(text_wide: &[u16]) -> Result<()> {
+    unsafe { EmptyClipboard() }?;
+
+    let memory = unsafe { GlobalAlloc(GMEM_MOVEABLE, std::mem::size_of_val(text_wide)) }?;
+    let buffer = unsafe { GlobalLock(memory) } as *mut u16;
+    if buffer.is_null() {
+        let error = Error::from_win32();
+        let _ = unsafe { GlobalFree(memory) };
+        return Err(error);
+    }
+    unsafe { std::ptr::copy_nonoverlapping(text_wide.as_ptr(), buffer, text_wide.len()) };
+    let _ = unsafe { GlobalUnlock(memory) };
+
+    if let Err(e) = unsafe { SetClipboardData(CF_UNICODETEXT.0.into(), HANDLE(memory.0 as isize)) } {
+        // the clipboard only takes ownership of the memory on success
+        let _ = unsafe { GlobalFree(memory) };
+        return Err(e);
+    }
+    Ok(())
+}
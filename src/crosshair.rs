@@ -0,0 +1,81 @@
+use windows::Win32::Foundation::COLORREF;
+
+use crate::gdi_primitives::rgb;
+
+
+// the length of each line, in drawing coordinates
+const DEFAULT_CROSSHAIR_LENGTH: i32 = 20;
+const CROSSHAIR_LENGTH_STEP: i32 = 10;
+const MIN_CROSSHAIR_LENGTH: i32 = 10;
+const MAX_CROSSHAIR_LENGTH: i32 = 400;
+
+
+// the first color while drawing, the second otherwise
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum CrosshairColors {
+    #[default]
+    RedBlue,
+    MagentaGreen,
+    OrangeBlack,
+}
+impl CrosshairColors {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::RedBlue => Self::MagentaGreen,
+            Self::MagentaGreen => Self::OrangeBlack,
+            Self::OrangeBlack => Self::RedBlue,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::RedBlue => "red-blue",
+            Self::MagentaGreen => "magenta-green",
+            Self::OrangeBlack => "orange-black",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::RedBlue, Self::MagentaGreen, Self::OrangeBlack]
+            .into_iter()
+            .find(|colors| colors.name() == name)
+    }
+
+    pub fn drawing(&self) -> COLORREF {
+        match self {
+            Self::RedBlue => rgb(0xFF, 0x00, 0x00),
+            Self::MagentaGreen => rgb(0xFF, 0x00, 0xFF),
+            Self::OrangeBlack => rgb(0xFF, 0x80, 0x00),
+        }
+    }
+
+    pub fn not_drawing(&self) -> COLORREF {
+        match self {
+            Self::RedBlue => rgb(0x00, 0x00, 0xFF),
+            Self::MagentaGreen => rgb(0x00, 0xA0, 0x00),
+            Self::OrangeBlack => rgb(0x00, 0x00, 0x00),
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct CrosshairLength(pub i32);
+impl CrosshairLength {
+    pub fn grow(&mut self) {
+        self.0 = (self.0 + CROSSHAIR_LENGTH_STEP).min(MAX_CROSSHAIR_LENGTH);
+    }
+
+    pub fn shrink(&mut self) {
+        self.0 = (self.0 - CROSSHAIR_LENGTH_STEP).max(MIN_CROSSHAIR_LENGTH);
+    }
+
+    pub fn clamped(length: i32) -> Self {
+        Self(length.clamp(MIN_CROSSHAIR_LENGTH, MAX_CROSSHAIR_LENGTH))
+    }
+}
+impl Default for CrosshairLength {
+    fn default() -> Self {
+        Self(DEFAULT_CROSSHAIR_LENGTH)
+    }
+}
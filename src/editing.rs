@@ -0,0 +1,380 @@
+use crate::{ApplicationState, Point};
+use crate::transform::translate_paths;
+
+
+// (path, point) indices in drawing order
+fn all_vertices(state: &ApplicationState) -> Vec<(usize, usize)> {
+    state.paths.iter()
+        .enumerate()
+        .flat_map(|(path_index, path)| (0..path.points.len()).map(move |point_index| (path_index, point_index)))
+        .collect()
+}
+
+
+pub(crate) fn cycle_vertex(state: &mut ApplicationState, forward: bool) {
+    let vertices = all_vertices(state);
+    state.selected_vertices.clear();
+    if vertices.len() == 0 {
+        state.vertex_cursor = None;
+        return;
+    }
+
+    let new_position = match state.vertex_cursor.and_then(|vc| vertices.iter().position(|v| *v == vc)) {
+        Some(pos) if forward => (pos + 1) % vertices.len(),
+        Some(pos) => (pos + vertices.len() - 1) % vertices.len(),
+        None if forward => 0,
+        None => vertices.len() - 1,
+    };
+    let vertex = vertices[new_position];
+    state.vertex_cursor = Some(vertex);
+    state.selected_vertices.insert(vertex);
+}
+
+
+// skips empty paths
+pub(crate) fn cycle_path(state: &mut ApplicationState, forward: bool) {
+    let candidates: Vec<usize> = state.paths.iter()
+        .enumerate()
+        .filter(|(_path_index, path)| path.points.len() > 0)
+        .map(|(path_index, _path)| path_index)
+        .collect();
+    let current = state.selected_paths.iter().next_back().copied();
+    state.selected_paths.clear();
+    if candidates.len() == 0 {
+        return;
+    }
+
+    let new_position = match current.and_then(|sp| candidates.iter().position(|c| *c == sp)) {
+        Some(pos) if forward => (pos + 1) % candidates.len(),
+        Some(pos) => (pos + candidates.len() - 1) % candidates.len(),
+        None if forward => 0,
+        None => candidates.len() - 1,
+    };
+    state.selected_paths.insert(candidates[new_position]);
+}
+
+
+pub(crate) fn delete_selected_paths(state: &mut ApplicationState) {
+    if state.selected_paths.len() == 0 {
+        state.log("no path selected");
+        return;
+    }
+    if state.is_drawing {
+        // the indices of the paths being drawn would shift
+        state.log("finish the current path before deleting one");
+        return;
+    }
+    // remove from the back so that the remaining indices stay valid
+    let path_indexes = std::mem::take(&mut state.selected_paths);
+    for &path_index in path_indexes.iter().rev() {
+        if path_index < state.paths.len() {
+            state.paths.remove(path_index);
+        }
+    }
+    if path_indexes.len() == 1 {
+        state.log(format!("deleted path {}", path_indexes.iter().next().unwrap()));
+    } else {
+        state.log(format!("deleted {} paths", path_indexes.len()));
+    }
+}
+
+
+// the copies are shifted by a cell so that they can be told apart
+pub(crate) fn duplicate_selected_paths(state: &mut ApplicationState) {
+    if state.is_drawing {
+        state.log("finish the current path before duplicating one");
+        return;
+    }
+    let path_indexes = state.selected_path_indexes();
+    let first_copy_index = state.paths.len();
+    for &path_index in &path_indexes {
+        let copy = state.paths[path_index].clone();
+        state.paths.push(copy);
+    }
+    let copy_indexes: Vec<usize> = (first_copy_index..state.paths.len()).collect();
+    translate_paths(&mut state.paths, &copy_indexes, state.canvas.horizontal_factor, state.canvas.vertical_factor);
+
+    // continue with the copies
+    state.edit_mode = false;
+    state.selected_vertices.clear();
+    state.vertex_cursor = None;
+    state.selected_paths = copy_indexes.into_iter().collect();
+}
+
+
+// a path left with fewer than two points can neither be seen nor sensibly edited, so it is
+// removed entirely
+pub(crate) fn delete_selected_vertices(state: &mut ApplicationState) {
+    if state.selected_vertices.len() == 0 {
+        state.log("no vertex selected");
+        return;
+    }
+
+    // remove from the back so that the indices of the remaining vertices stay valid
+    let vertices = std::mem::take(&mut state.selected_vertices);
+    let mut removed_paths = Vec::new();
+    for &(path_index, point_index) in vertices.iter().rev() {
+        let path = &mut state.paths[path_index];
+        path.remove_point(point_index);
+        path.cell = false;
+        if path.points.len() < 2 && removed_paths.last() != Some(&path_index) {
+            removed_paths.push(path_index);
+        }
+    }
+    for &path_index in &removed_paths {
+        state.paths.remove(path_index);
+    }
+
+    state.vertex_cursor = state.vertex_cursor
+        .filter(|(path_index, _point_index)| !removed_paths.contains(path_index))
+        .map(|(path_index, point_index)| {
+            let removed_before = vertices.range((path_index, 0)..(path_index, point_index)).count();
+            let new_path_index = path_index - removed_paths.iter().filter(|&&rp| rp < path_index).count();
+            let point_count = state.paths[new_path_index].points.len();
+            let new_point_index = (point_index - removed_before).saturating_sub(1).min(point_count - 1);
+            (new_path_index, new_point_index)
+        });
+    state.selected_vertices.extend(state.vertex_cursor);
+
+    state.log(format!("deleted {} vertices", vertices.len()));
+    if removed_paths.len() > 0 {
+        state.log(format!("removed {} paths left with fewer than two points", removed_paths.len()));
+    }
+}
+
+
+pub(crate) fn extend_selection(state: &mut ApplicationState, forward: bool) {
+    let (path_index, point_index) = match state.vertex_cursor {
+        Some(vc) => vc,
+        None => {
+            cycle_vertex(state, forward);
+            return;
+        },
+    };
+    let point_count = state.paths.get(path_index).map_or(0, |path| path.points.len());
+    if point_count == 0 {
+        // the vertex is gone; start over
+        cycle_vertex(state, forward);
+        return;
+    }
+    let new_point_index = if forward {
+        (point_index + 1) % point_count
+    } else {
+        (point_index + point_count - 1) % point_count
+    };
+    let vertex = (path_index, new_point_index);
+    state.vertex_cursor = Some(vertex);
+    state.selected_vertices.insert(vertex);
+}
+
+
+// only the new point is selected so that it can be moved right away
+pub(crate) fn insert_midpoint(state: &mut ApplicationState) {
+    let (path_index, point_index) = match state.vertex_cursor {
+        Some(vc) => vc,
+        None => {
+            state.log("no vertex selected");
+            return;
+        },
+    };
+    let path = &state.paths[path_index];
+    let range = match path.subpath_ranges().into_iter().find(|range| range.contains(&point_index)) {
+        Some(range) => range,
+        None => return,
+    };
+    let next_index = if point_index + 1 < range.end {
+        point_index + 1
+    } else if path.closed && range.len() > 1 {
+        // the closing edge leads back to the start of the subpath
+        range.start
+    } else {
+        state.log("no edge follows the selected vertex");
+        return;
+    };
+    let (start, end) = (path.points[point_index], path.points[next_index]);
+    let midpoint = Point {
+        x: start.x + (end.x - start.x) / 2,
+        y: start.y + (end.y - start.y) / 2,
+    };
+
+    // on the closing edge, this appends to the subpath
+    let new_index = point_index + 1;
+    let path = &mut state.paths[path_index];
+    path.insert_point(new_index, midpoint);
+    path.cell = false;
+
+    // the indices of later vertices have shifted
+    let vertex = (path_index, new_index);
+    state.selected_vertices.clear();
+    state.selected_vertices.insert(vertex);
+    state.vertex_cursor = Some(vertex);
+}
+
+
+// keeps coordinates non-negative and, if the cursor is kept on the grid, within the grid
+pub(crate) fn move_selection(state: &mut ApplicationState, delta: Point) {
+    let extent = state.grid_extent();
+    for &(path_index, point_index) in &state.selected_vertices {
+        let path = &mut state.paths[path_index];
+        let point = &mut path.points[point_index];
+        point.x = (point.x + delta.x).max(0);
+        point.y = (point.y + delta.y).max(0);
+        if state.clamp_to_grid {
+            point.x = point.x.min(extent.x);
+            point.y = point.y.min(extent.y);
+        }
+
+        // a painted cell with a moved corner is no longer a cell
+        path.cell = false;
+    }
+}
+
+
+pub(crate) fn prune_selection(state: &mut ApplicationState) {
+    let paths = &state.paths;
+    let exists = |&(path_index, point_index): &(usize, usize)| {
+        path_index < paths.len() && point_index < paths[path_index].points.len()
+    };
+    state.selected_vertices.retain(exists);
+    if !state.vertex_cursor.map(|vc| exists(&vc)).unwrap_or(true) {
+        state.vertex_cursor = state.selected_vertices.iter().next_back().copied();
+    }
+    state.selected_paths.retain(|&path_index| path_index < paths.len());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClosedPath;
+
+    fn state_with_cursor(paths: Vec<ClosedPath>, vertex: (usize, usize)) -> ApplicationState {
+        let mut state = ApplicationState {
+            paths,
+            vertex_cursor: Some(vertex),
+            ..ApplicationState::default()
+        };
+        state.selected_vertices.insert(vertex);
+        state
+    }
+
+    #[test]
+    fn duplicated_square_is_shifted_by_a_cell() {
+        let square = ClosedPath {
+            color: 0x0000C0,
+            closed: false,
+            ..ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100), (0, 100)])
+        };
+        let mut state = ApplicationState {
+            paths: vec![square.clone()],
+            ..ApplicationState::default()
+        };
+        state.selected_paths.insert(0);
+        duplicate_selected_paths(&mut state);
+
+        let copy = ClosedPath {
+            points: ClosedPath::from_coords(&[(100, 100), (200, 100), (200, 200), (100, 200)]).points,
+            ..square.clone()
+        };
+        assert_eq!(state.paths, vec![square.clone(), copy]);
+        assert_eq!(state.selected_paths.iter().copied().collect::<Vec<_>>(), vec![1]);
+
+        // the copy is independent of the original
+        state.paths[1].points[0] = Point { x: 500, y: 500 };
+        assert_eq!(state.paths[0], square);
+    }
+
+    #[test]
+    fn selected_paths_are_deleted() {
+        let paths = vec![
+            ClosedPath::from_coords(&[(0, 0), (100, 0)]),
+            ClosedPath::from_coords(&[(0, 100), (100, 100)]),
+            ClosedPath::from_coords(&[(0, 200), (100, 200)]),
+        ];
+        let mut state = ApplicationState {
+            paths: paths.clone(),
+            selected_paths: [0, 2].into_iter().collect(),
+            ..ApplicationState::default()
+        };
+        delete_selected_paths(&mut state);
+        assert_eq!(state.paths, vec![paths[1].clone()]);
+        assert_eq!(state.selected_paths.len(), 0);
+
+        // not while drawing, as the path being drawn is the last one
+        state.selected_paths.insert(0);
+        state.is_drawing = true;
+        delete_selected_paths(&mut state);
+        assert_eq!(state.paths, vec![paths[1].clone()]);
+    }
+
+    #[test]
+    fn deleted_vertices_leave_the_previous_one_selected() {
+        let mut holed = ClosedPath::from_coords(&[(0, 0), (400, 0), (400, 400), (100, 100), (300, 100), (300, 300)]);
+        holed.subpath_starts = vec![3];
+        holed.control_points = vec![5];
+        let mut state = state_with_cursor(vec![holed], (0, 4));
+        state.selected_vertices.insert((0, 1));
+        delete_selected_vertices(&mut state);
+
+        let remaining = ClosedPath::from_coords(&[(0, 0), (400, 400), (100, 100), (300, 300)]);
+        assert_eq!(state.paths[0].points, remaining.points);
+        assert_eq!(state.paths[0].subpath_starts, vec![2]);
+        assert_eq!(state.paths[0].control_points, vec![3]);
+        assert_eq!(state.vertex_cursor, Some((0, 2)));
+        assert_eq!(state.selected_vertices.iter().copied().collect::<Vec<_>>(), vec![(0, 2)]);
+    }
+
+    #[test]
+    fn path_left_with_one_point_is_removed() {
+        let paths = vec![
+            ClosedPath::from_coords(&[(0, 0), (100, 0)]),
+            ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100)]),
+        ];
+        let mut state = state_with_cursor(paths, (0, 1));
+        delete_selected_vertices(&mut state);
+        assert_eq!(state.paths, vec![ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100)])]);
+        assert_eq!(state.vertex_cursor, None);
+        assert_eq!(state.selected_vertices.len(), 0);
+    }
+
+    #[test]
+    fn midpoint_is_inserted_after_the_selected_vertex() {
+        let mut state = state_with_cursor(vec![ClosedPath::from_coords(&[(0, 0), (200, 0), (200, 200)])], (0, 0));
+        insert_midpoint(&mut state);
+        assert_eq!(state.paths[0].points, ClosedPath::from_coords(&[(0, 0), (100, 0), (200, 0), (200, 200)]).points);
+        assert_eq!(state.vertex_cursor, Some((0, 1)));
+        assert_eq!(state.selected_vertices.iter().copied().collect::<Vec<_>>(), vec![(0, 1)]);
+
+        // the new point can be moved right away
+        move_selection(&mut state, Point { x: 0, y: 50 });
+        assert_eq!(state.paths[0].points[1], Point { x: 100, y: 50 });
+    }
+
+    #[test]
+    fn midpoint_of_the_closing_edge_is_appended() {
+        let mut state = state_with_cursor(vec![ClosedPath::from_coords(&[(0, 0), (200, 0), (200, 200)])], (0, 2));
+        insert_midpoint(&mut state);
+        assert_eq!(state.paths[0].points, ClosedPath::from_coords(&[(0, 0), (200, 0), (200, 200), (100, 100)]).points);
+        assert_eq!(state.vertex_cursor, Some((0, 3)));
+    }
+
+    #[test]
+    fn midpoint_of_a_closing_edge_stays_in_its_subpath() {
+        let mut holed = ClosedPath::from_coords(&[(0, 0), (400, 0), (400, 400), (100, 100), (300, 100), (300, 300)]);
+        holed.subpath_starts = vec![3];
+        let mut state = state_with_cursor(vec![holed], (0, 2));
+        insert_midpoint(&mut state);
+        assert_eq!(state.paths[0].points[3], Point { x: 200, y: 200 });
+        assert_eq!(state.paths[0].subpath_starts, vec![4]);
+    }
+
+    #[test]
+    fn open_path_has_no_edge_after_its_last_vertex() {
+        let mut open = ClosedPath::from_coords(&[(0, 0), (200, 0)]);
+        open.closed = false;
+        let mut state = state_with_cursor(vec![open.clone()], (0, 1));
+        insert_midpoint(&mut state);
+        assert_eq!(state.paths, vec![open]);
+        assert_eq!(state.vertex_cursor, Some((0, 1)));
+    }
+}
@@ -1,45 +1,117 @@
-use windows::Win32::Foundation::COLORREF;
+use std::ffi::c_void;
+use std::sync::Mutex;
+
+use windows::core::{Error, Result};
+use windows::Win32::Foundation::{COLORREF, POINT, RECT};
 use windows::Win32::Graphics::Gdi::{
-    BeginPath, BS_SOLID, CloseFigure, CreateSolidBrush, EndPath, ExtCreatePen, FillPath, HBRUSH,
-    HDC, HGDIOBJ, HPEN, LineTo, LOGBRUSH, MoveToEx, PEN_STYLE, PS_ENDCAP_SQUARE, PS_GEOMETRIC,
-    PS_SOLID, SelectObject, StrokePath,
+    AC_SRC_OVER, AlphaBlend, BeginPath, BI_RGB, BITMAPINFO, BITMAPINFOHEADER, BLENDFUNCTION, BS_SOLID,
+    CloseFigure, CreateCompatibleBitmap, CreateCompatibleDC, CreateDIBSection, CreateFontIndirectW,
+    CreateSolidBrush, DeleteDC, DeleteObject, DIB_RGB_COLORS, EndPath, ExtCreatePen, FillPath, FillRect, GetClipBox, GetCurrentObject, HBITMAP, HBRUSH, HDC, HFONT,
+    HGDIOBJ, HPEN, HRGN, LineTo, LOGBRUSH, LOGFONTW, MoveToEx, OBJ_TYPE, PEN_STYLE, PolyBezierTo, PS_ENDCAP_FLAT,
+    PS_ENDCAP_SQUARE, PS_GEOMETRIC, PS_JOIN_ROUND, PS_SOLID, PS_USERSTYLE, RGN_COPY, SelectClipPath,
+    SelectClipRgn, SelectObject, StrokePath,
 };
 
+// the path functions record failures here instead of panicking, as a panic while painting
+// would unwind into Windows; whoever paints checks for a failure once done
+static FAILURE: Mutex<Option<String>> = Mutex::new(None);
+
+// an earlier failure that has not been taken yet is kept
+pub(crate) fn record_failure_message(message: String) {
+    let mut failure = FAILURE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if failure.is_none() {
+        *failure = Some(message);
+    }
+}
+
+fn record_failure(action: &str, result: Result<()>) {
+    if let Err(e) = result {
+        record_failure_message(format!("failed to {}: {}", action, e));
+    }
+}
+
+pub(crate) fn take_failure() -> Option<String> {
+    FAILURE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take()
+}
+
+fn check(succeeded: bool) -> Result<()> {
+    if succeeded {
+        Ok(())
+    } else {
+        Err(Error::from_win32())
+    }
+}
+
 macro_rules! simple_gdi_func {
-    ($name:ident, $func:ident, $panic_text:expr) => {
+    ($name:ident, $try_name:ident, $func:ident, $action:expr) => {
+        pub(crate) fn $try_name(hdc: HDC) -> Result<()> {
+            check(unsafe { $func(hdc) }.as_bool())
+        }
+
         pub(crate) fn $name(hdc: HDC) {
-            let result = unsafe { $func(hdc) };
-            if !result.as_bool() {
-                panic!($panic_text);
-            }
+            record_failure($action, $try_name(hdc));
         }
     };
 }
-simple_gdi_func!(begin_path, BeginPath, "failed to begin path");
-simple_gdi_func!(close_figure, CloseFigure, "failed to close figure");
-simple_gdi_func!(end_path, EndPath, "failed to end path");
-simple_gdi_func!(fill_path, FillPath, "failed to fill path");
-simple_gdi_func!(stroke_path, StrokePath, "failed to stroke path");
+simple_gdi_func!(begin_path, try_begin_path, BeginPath, "begin path");
+simple_gdi_func!(close_figure, try_close_figure, CloseFigure, "close figure");
+simple_gdi_func!(end_path, try_end_path, EndPath, "end path");
+simple_gdi_func!(fill_path, try_fill_path, FillPath, "fill path");
+simple_gdi_func!(stroke_path, try_stroke_path, StrokePath, "stroke path");
 
-pub(crate) fn select_object<O: Into<HGDIOBJ>>(hdc: HDC, object: O, description: &str) {
-    let selected = unsafe { SelectObject(hdc, object.into()) };
-    if selected.is_invalid() {
-        panic!("failed to select {}", description);
+// returns the previous object, which is invalid if selecting failed
+pub(crate) fn select_object<O: Into<HGDIOBJ>>(hdc: HDC, object: O, description: &str) -> HGDIOBJ {
+    let previous = unsafe { SelectObject(hdc, object.into()) };
+    if previous.is_invalid() {
+        record_failure(&format!("select {}", description), Err(Error::from_win32()));
     }
+    previous
 }
 
-pub(crate) fn move_to(hdc: HDC, x: i32, y: i32) {
-    let moved = unsafe { MoveToEx(hdc, x, y, None) };
-    if !moved.as_bool() {
-        panic!("failed to move");
+pub(crate) struct SelectionGuard {
+    hdc: HDC,
+    previous: HGDIOBJ,
+}
+impl Drop for SelectionGuard {
+    fn drop(&mut self) {
+        if !self.previous.is_invalid() {
+            unsafe { SelectObject(self.hdc, self.previous) };
+        }
     }
 }
 
+pub(crate) fn select_object_scoped<O: Into<HGDIOBJ>>(hdc: HDC, object: O, description: &str) -> SelectionGuard {
+    let previous = select_object(hdc, object, description);
+    SelectionGuard { hdc, previous }
+}
+
+pub(crate) fn preserve_selection(hdc: HDC, object_type: OBJ_TYPE) -> SelectionGuard {
+    let previous = unsafe { GetCurrentObject(hdc, object_type) };
+    SelectionGuard { hdc, previous }
+}
+
+pub(crate) fn try_move_to(hdc: HDC, x: i32, y: i32) -> Result<()> {
+    check(unsafe { MoveToEx(hdc, x, y, None) }.as_bool())
+}
+
+pub(crate) fn move_to(hdc: HDC, x: i32, y: i32) {
+    record_failure("move", try_move_to(hdc, x, y));
+}
+
+pub(crate) fn try_line_to(hdc: HDC, x: i32, y: i32) -> Result<()> {
+    check(unsafe { LineTo(hdc, x, y) }.as_bool())
+}
+
 pub(crate) fn line_to(hdc: HDC, x: i32, y: i32) {
-    let lined = unsafe { LineTo(hdc, x, y) };
-    if !lined.as_bool() {
-        panic!("failed to add line");
-    }
+    record_failure("add line", try_line_to(hdc, x, y));
+}
+
+pub(crate) fn try_poly_bezier_to(hdc: HDC, points: &[POINT]) -> Result<()> {
+    check(unsafe { PolyBezierTo(hdc, points) }.as_bool())
+}
+
+pub(crate) fn poly_bezier_to(hdc: HDC, points: &[POINT]) {
+    record_failure("add Bézier curves", try_poly_bezier_to(hdc, points));
 }
 
 pub(crate) const fn rgb(r: u8, g: u8, b: u8) -> COLORREF {
@@ -50,32 +122,219 @@ pub(crate) const fn rgb(r: u8, g: u8, b: u8) -> COLORREF {
     COLORREF(color)
 }
 
-pub(crate) fn ext_create_pen(style: PEN_STYLE, width: u32, brush: &LOGBRUSH, dashes: Option<&[u32]>) -> HPEN {
+// must not be selected into a device context when dropped; if creating it failed, the handle
+// is invalid and the failure has been recorded
+#[derive(Debug)]
+pub(crate) struct OwnedObject<H: Copy + Into<HGDIOBJ>>(H);
+impl<H: Copy + Into<HGDIOBJ>> OwnedObject<H> {
+    pub fn handle(&self) -> H {
+        self.0
+    }
+}
+impl<H: Copy + Into<HGDIOBJ>> Drop for OwnedObject<H> {
+    fn drop(&mut self) {
+        let object: HGDIOBJ = self.0.into();
+        if !object.is_invalid() {
+            unsafe { DeleteObject(object) };
+        }
+    }
+}
+
+// objects selected into it must be selected out again before it is dropped
+#[derive(Debug)]
+pub(crate) struct OwnedDc(HDC);
+impl OwnedDc {
+    pub fn handle(&self) -> HDC {
+        self.0
+    }
+}
+impl Drop for OwnedDc {
+    fn drop(&mut self) {
+        if !self.0.is_invalid() {
+            unsafe { DeleteDC(self.0) };
+        }
+    }
+}
+
+// with the screen if `hdc` is null
+pub(crate) fn make_memory_dc(hdc: HDC) -> OwnedDc {
+    let memory_dc = unsafe { CreateCompatibleDC(hdc) };
+    if memory_dc.is_invalid() {
+        record_failure("create memory device context", Err(Error::from_win32()));
+    }
+    OwnedDc(memory_dc)
+}
+
+pub(crate) type OwnedPen = OwnedObject<HPEN>;
+pub(crate) type OwnedBrush = OwnedObject<HBRUSH>;
+pub(crate) type OwnedFont = OwnedObject<HFONT>;
+pub(crate) type OwnedBitmap = OwnedObject<HBITMAP>;
+
+pub(crate) fn ext_create_pen(style: PEN_STYLE, width: u32, brush: &LOGBRUSH, dashes: Option<&[u32]>) -> OwnedPen {
     let pen = unsafe { ExtCreatePen(style, width, brush, dashes) };
     if pen.is_invalid() {
-        panic!("failed to create pen");
+        record_failure("create pen", Err(Error::from_win32()));
     }
-    pen
+    OwnedObject(pen)
 }
 
-pub(crate) fn make_solid_square_endcap_pen(width: u32, color: COLORREF) -> HPEN {
+pub(crate) fn make_solid_pen(width: u32, color: COLORREF, cap: PEN_STYLE, join: PEN_STYLE) -> OwnedPen {
     let brush = LOGBRUSH {
         lbColor: color,
         lbStyle: BS_SOLID,
         lbHatch: 0,
     };
     ext_create_pen(
-        PS_GEOMETRIC | PS_SOLID | PS_ENDCAP_SQUARE,
+        PS_GEOMETRIC | PS_SOLID | cap | join,
         width,
         &brush,
         None,
     )
 }
 
-pub(crate) fn make_solid_brush(color: COLORREF) -> HBRUSH {
+pub(crate) fn make_solid_square_endcap_pen(width: u32, color: COLORREF) -> OwnedPen {
+    make_solid_pen(width, color, PS_ENDCAP_SQUARE, PS_JOIN_ROUND)
+}
+
+// dashes alternate between drawn and skipped lengths, starting drawn; no dashes means solid
+pub(crate) fn make_dashed_pen(width: u32, color: COLORREF, dashes: &[u32]) -> OwnedPen {
+    if dashes.len() == 0 {
+        return make_solid_pen(width, color, PS_ENDCAP_FLAT, PS_JOIN_ROUND);
+    }
+    let brush = LOGBRUSH {
+        lbColor: color,
+        lbStyle: BS_SOLID,
+        lbHatch: 0,
+    };
+    ext_create_pen(
+        PS_GEOMETRIC | PS_USERSTYLE | PS_ENDCAP_FLAT,
+        width,
+        &brush,
+        Some(dashes),
+    )
+}
+
+pub(crate) fn fill_rect_translucent(hdc: HDC, rect: &RECT, brush: HBRUSH, opacity: u8) {
+    // dropped in reverse order: the pixel is selected out before it and the DC are deleted
+    let memory_dc = make_memory_dc(hdc);
+    let pixel = make_compatible_bitmap(hdc, 1, 1);
+    if memory_dc.handle().is_invalid() || pixel.handle().is_invalid() {
+        return;
+    }
+    let _pixel_guard = select_object_scoped(memory_dc.handle(), pixel.handle(), "pixel bitmap");
+    let pixel_rect = RECT {
+        left: 0,
+        top: 0,
+        right: 1,
+        bottom: 1,
+    };
+    unsafe { FillRect(memory_dc.handle(), &pixel_rect, brush) };
+
+    let blend = BLENDFUNCTION {
+        BlendOp: AC_SRC_OVER as u8,
+        BlendFlags: 0,
+        SourceConstantAlpha: opacity,
+        AlphaFormat: 0,
+    };
+    unsafe {
+        AlphaBlend(
+            hdc,
+            rect.left,
+            rect.top,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            memory_dc.handle(),
+            0,
+            0,
+            1,
+            1,
+            blend,
+        )
+    };
+}
+
+// replaces the clipping region of the device context, which is removed afterwards
+pub(crate) fn fill_path_translucent(hdc: HDC, brush: HBRUSH, opacity: u8) {
+    // blend a rectangle, but only inside the path
+    let clipped = check(unsafe { SelectClipPath(hdc, RGN_COPY) }.as_bool());
+    if clipped.is_err() {
+        record_failure("clip to path", clipped);
+        return;
+    }
+    let mut clip_box = RECT::default();
+    unsafe { GetClipBox(hdc, &mut clip_box) };
+    fill_rect_translucent(hdc, &clip_box, brush, opacity);
+    unsafe { SelectClipRgn(hdc, HRGN::default()) };
+}
+
+pub(crate) fn make_compatible_bitmap(hdc: HDC, width: i32, height: i32) -> OwnedBitmap {
+    let bitmap = unsafe { CreateCompatibleBitmap(hdc, width, height) };
+    if bitmap.is_invalid() {
+        record_failure("create compatible bitmap", Err(Error::from_win32()));
+    }
+    OwnedObject(bitmap)
+}
+
+// 32 bits per pixel, top-down and transparent black; the pointer is null if creating it failed
+pub(crate) fn make_dib_section(hdc: HDC, width: i32, height: i32) -> (OwnedBitmap, *mut u8) {
+    let mut bitmap_info = BITMAPINFO::default();
+    bitmap_info.bmiHeader.biSize = std::mem::size_of::<BITMAPINFOHEADER>().try_into().unwrap();
+    bitmap_info.bmiHeader.biWidth = width;
+    bitmap_info.bmiHeader.biHeight = -height; // top-down
+    bitmap_info.bmiHeader.biPlanes = 1;
+    bitmap_info.bmiHeader.biBitCount = 32;
+    bitmap_info.bmiHeader.biCompression = BI_RGB.0;
+
+    let mut bits: *mut c_void = std::ptr::null_mut();
+    match unsafe { CreateDIBSection(hdc, &bitmap_info, DIB_RGB_COLORS, &mut bits, None, 0) } {
+        Ok(bitmap) => (OwnedObject(bitmap), bits as *mut u8),
+        Err(e) => {
+            record_failure("create DIB section", Err(e));
+            (OwnedObject(HBITMAP::default()), std::ptr::null_mut())
+        },
+    }
+}
+
+pub(crate) fn make_solid_brush(color: COLORREF) -> OwnedBrush {
     let brush = unsafe { CreateSolidBrush(color) };
     if brush.is_invalid() {
-        panic!("failed to create solid brush");
+        record_failure("create solid brush", Err(Error::from_win32()));
+    }
+    OwnedObject(brush)
+}
+
+pub(crate) fn make_font(height: i32, face: &str) -> OwnedFont {
+    let mut log_font = LOGFONTW {
+        // negative heights match the character height instead of the cell height
+        lfHeight: -height,
+        ..LOGFONTW::default()
+    };
+    // keep the terminating NUL
+    let face_length = log_font.lfFaceName.len() - 1;
+    for (target, source) in log_font.lfFaceName.iter_mut().zip(face.encode_utf16().take(face_length)) {
+        *target = source;
+    }
+    let font = unsafe { CreateFontIndirectW(&log_font) };
+    if font.is_invalid() {
+        record_failure("create font", Err(Error::from_win32()));
+    }
+    OwnedObject(font)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropped_objects_do_not_exhaust_gdi_handles() {
+        // a process can have at most 10,000 GDI objects by default
+        for i in 0..20_000u32 {
+            let pen = make_solid_square_endcap_pen(1, COLORREF(i));
+            let brush = make_solid_brush(COLORREF(i));
+            assert!(!pen.handle().is_invalid(), "creating pen {} failed", i);
+            assert!(!brush.handle().is_invalid(), "creating brush {} failed", i);
+        }
+        assert_eq!(take_failure(), None);
     }
-    brush
 }
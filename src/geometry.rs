@@ -0,0 +1,305 @@
+use std::ops::Range;
+
+use crate::{CanvasConfig, ClosedPath, Point};
+use crate::spline::Segment;
+
+
+// the check compares every pair of edges and runs whenever the window is painted
+const MAX_INTERSECTION_CHECK_EDGES: usize = 256;
+
+
+impl ClosedPath {
+    pub fn bounding_box(&self) -> Option<(Point, Point)> {
+        let first = *self.points.first()?;
+        let mut min = first;
+        let mut max = first;
+        for point in &self.points {
+            min.x = min.x.min(point.x);
+            min.y = min.y.min(point.y);
+            max.x = max.x.max(point.x);
+            max.y = max.y.max(point.y);
+        }
+        Some((min, max))
+    }
+
+    // empty subpaths are skipped
+    pub fn subpath_ranges(&self) -> Vec<Range<usize>> {
+        let mut ret = Vec::new();
+        let mut start = 0;
+        for &end in self.subpath_starts.iter().chain(std::iter::once(&self.points.len())) {
+            let end = end.min(self.points.len());
+            if end > start {
+                ret.push(start..end);
+                start = end;
+            }
+        }
+        ret
+    }
+
+    pub fn subpaths(&self) -> Vec<&[Point]> {
+        self.subpath_ranges()
+            .into_iter()
+            .map(|range| &self.points[range])
+            .collect()
+    }
+
+    // one control point between two points makes a quadratic curve, two a cubic one; further
+    // control points in a row and trailing ones of an open path count as corners
+    pub fn segments(&self, range: Range<usize>) -> Vec<Segment> {
+        let mut ret = Vec::new();
+        let mut controls = Vec::new();
+        for i in (range.start + 1)..range.end {
+            if self.control_points.contains(&i) {
+                controls.push(self.points[i]);
+            } else {
+                push_curve(&mut ret, &mut controls, self.points[i]);
+            }
+        }
+        if controls.len() > 0 {
+            if self.closed {
+                // curve back to the start of the subpath
+                push_curve(&mut ret, &mut controls, self.points[range.start]);
+            } else {
+                ret.extend(controls.drain(..).map(Segment::Line));
+            }
+        }
+        ret
+    }
+
+    // shoelace formula; subpaths wound against the rest of the path are subtracted as holes
+    pub fn area(&self) -> f64 {
+        let twice_area: i64 = self.subpaths()
+            .into_iter()
+            .map(twice_signed_area)
+            .sum();
+        (twice_area as f64 / 2.0).abs()
+    }
+
+    pub fn perimeter(&self) -> f64 {
+        self.subpaths()
+            .into_iter()
+            .map(|subpath| outline_length(subpath, self.closed, 1.0, 1.0))
+            .sum()
+    }
+
+    pub fn area_in_cells(&self, canvas: CanvasConfig) -> f64 {
+        self.area() / (f64::from(canvas.horizontal_factor) * f64::from(canvas.vertical_factor))
+    }
+
+    // cells may be taller than wide
+    pub fn perimeter_in_cells(&self, canvas: CanvasConfig) -> f64 {
+        let x_unit = f64::from(canvas.horizontal_factor);
+        let y_unit = f64::from(canvas.vertical_factor);
+        self.subpaths()
+            .into_iter()
+            .map(|subpath| outline_length(subpath, self.closed, x_unit, y_unit))
+            .sum()
+    }
+
+    // curves are approximated by the lines between their control points; paths with too many
+    // edges are reported as not intersecting to keep the window responsive
+    pub fn self_intersects(&self) -> bool {
+        // (subpath index, edge index within the subpath, edge count of the subpath, start, end)
+        let mut edges = Vec::new();
+        for (subpath_index, subpath) in self.subpaths().into_iter().enumerate() {
+            let mut corners = subpath.to_vec();
+            corners.dedup();
+            if self.closed && corners.len() > 1 && corners.first() == corners.last() {
+                corners.pop();
+            }
+            let count = corners.len();
+            if count < 2 {
+                continue;
+            }
+            let edge_count = if self.closed && count > 2 { count } else { count - 1 };
+            for i in 0..edge_count {
+                edges.push((subpath_index, i, edge_count, corners[i], corners[(i + 1) % count]));
+            }
+        }
+        if edges.len() > MAX_INTERSECTION_CHECK_EDGES {
+            return false;
+        }
+
+        for (i, &(subpath_a, index_a, count_a, start_a, end_a)) in edges.iter().enumerate() {
+            for &(subpath_b, index_b, _count_b, start_b, end_b) in &edges[i+1..] {
+                if subpath_a == subpath_b {
+                    let follows = index_b == index_a + 1
+                        || (self.closed && index_a == 0 && index_b == count_a - 1);
+                    if follows {
+                        continue;
+                    }
+                }
+                if segments_intersect(start_a, end_a, start_b, end_b) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+
+fn push_curve(segments: &mut Vec<Segment>, controls: &mut Vec<Point>, end: Point) {
+    let corner_count = controls.len().saturating_sub(2);
+    segments.extend(controls.drain(..corner_count).map(Segment::Line));
+    let segment = match controls.as_slice() {
+        [] => Segment::Line(end),
+        [control] => Segment::Quad(*control, end),
+        [control1, control2] => Segment::Cubic(*control1, *control2, end),
+        _ => unreachable!("more than two control points left"),
+    };
+    segments.push(segment);
+    controls.clear();
+}
+
+
+fn twice_signed_area(points: &[Point]) -> i64 {
+    let count = points.len();
+    if count < 3 {
+        return 0;
+    }
+    let mut twice_area: i64 = 0;
+    for i in 0..count {
+        let a = points[i];
+        let b = points[(i + 1) % count];
+        twice_area += i64::from(a.x) * i64::from(b.y) - i64::from(b.x) * i64::from(a.y);
+    }
+    twice_area
+}
+
+
+fn orientation(a: Point, b: Point, c: Point) -> i64 {
+    let cross = (i64::from(b.x) - i64::from(a.x)) * (i64::from(c.y) - i64::from(a.y))
+        - (i64::from(b.y) - i64::from(a.y)) * (i64::from(c.x) - i64::from(a.x));
+    cross.signum()
+}
+
+
+// `c` is known to lie on the line through `a` and `b`
+fn within_segment(a: Point, b: Point, c: Point) -> bool {
+    c.x >= a.x.min(b.x) && c.x <= a.x.max(b.x) && c.y >= a.y.min(b.y) && c.y <= a.y.max(b.y)
+}
+
+
+fn segments_intersect(a1: Point, a2: Point, b1: Point, b2: Point) -> bool {
+    let o1 = orientation(a1, a2, b1);
+    let o2 = orientation(a1, a2, b2);
+    let o3 = orientation(b1, b2, a1);
+    let o4 = orientation(b1, b2, a2);
+    if o1 != o2 && o3 != o4 {
+        // the endpoints of each segment lie on different sides of the other
+        return true;
+    }
+    (o1 == 0 && within_segment(a1, a2, b1))
+        || (o2 == 0 && within_segment(a1, a2, b2))
+        || (o3 == 0 && within_segment(b1, b2, a1))
+        || (o4 == 0 && within_segment(b1, b2, a2))
+}
+
+
+fn outline_length(points: &[Point], closed: bool, x_unit: f64, y_unit: f64) -> f64 {
+    let count = points.len();
+    if count < 2 {
+        return 0.0;
+    }
+    let edge_count = if closed { count } else { count - 1 };
+    (0..edge_count)
+        .map(|i| {
+            let a = points[i];
+            let b = points[(i + 1) % count];
+            let dx = f64::from(b.x - a.x) / x_unit;
+            let dy = f64::from(b.y - a.y) / y_unit;
+            dx.hypot(dy)
+        })
+        .sum()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_square_measures_one_cell() {
+        let canvas = CanvasConfig::default();
+        let square = ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100), (0, 100)]);
+        assert_eq!(square.area(), 10_000.0);
+        assert_eq!(square.perimeter(), 400.0);
+        assert_eq!(square.area_in_cells(canvas), 1.0);
+        assert_eq!(square.perimeter_in_cells(canvas), 4.0);
+
+        // the winding direction does not matter
+        let mut reversed = square.clone();
+        reversed.points.reverse();
+        assert_eq!(reversed.area(), 10_000.0);
+    }
+
+    #[test]
+    fn triangle_has_the_known_area() {
+        let triangle = ClosedPath::from_coords(&[(0, 0), (300, 0), (0, 400)]);
+        assert_eq!(triangle.area(), 60_000.0);
+        assert_eq!(triangle.perimeter(), 1200.0);
+
+        // cells need not be square
+        let canvas = CanvasConfig { horizontal_factor: 300, vertical_factor: 400, ..CanvasConfig::default() };
+        assert_eq!(triangle.area_in_cells(canvas), 0.5);
+        assert_eq!(triangle.perimeter_in_cells(canvas), 2.0 + 2.0f64.sqrt());
+    }
+
+    #[test]
+    fn open_path_has_no_closing_edge() {
+        let mut open = ClosedPath::from_coords(&[(0, 0), (300, 0), (0, 400)]);
+        open.closed = false;
+        assert_eq!(open.perimeter(), 800.0);
+    }
+
+    #[test]
+    fn crossing_edges_are_detected() {
+        let bow_tie = ClosedPath::from_coords(&[(0, 0), (200, 200), (200, 0), (0, 200)]);
+        assert!(bow_tie.self_intersects());
+        let square = ClosedPath::from_coords(&[(0, 0), (200, 0), (200, 200), (0, 200)]);
+        assert!(!square.self_intersects());
+
+        // a corner touching another edge counts as well
+        let touching = ClosedPath::from_coords(&[(0, 0), (400, 0), (400, 400), (200, 0), (0, 400)]);
+        assert!(touching.self_intersects());
+    }
+
+    #[test]
+    fn closing_edge_only_counts_for_closed_paths() {
+        let mut zigzag = ClosedPath::from_coords(&[(0, 0), (200, 0), (0, 200), (200, 200)]);
+        assert!(zigzag.self_intersects());
+        zigzag.closed = false;
+        assert!(!zigzag.self_intersects());
+
+        // repeating the first point at the end does not make the path cross itself
+        let repeated_start = ClosedPath::from_coords(&[(0, 0), (200, 0), (200, 200), (0, 0)]);
+        assert!(!repeated_start.self_intersects());
+    }
+
+    #[test]
+    fn subpaths_crossing_each_other_are_detected() {
+        let mut overlapping = ClosedPath::from_coords(&[
+            (0, 0), (200, 0), (200, 200), (0, 200), (100, 100), (300, 100), (300, 300),
+        ]);
+        overlapping.subpath_starts = vec![4];
+        assert!(overlapping.self_intersects());
+
+        let mut donut = ClosedPath::from_coords(&[
+            (0, 0), (400, 0), (400, 400), (0, 400), (100, 100), (300, 100), (300, 300),
+        ]);
+        donut.subpath_starts = vec![4];
+        assert!(!donut.self_intersects());
+    }
+
+    #[test]
+    fn degenerate_paths_measure_nothing() {
+        assert_eq!(ClosedPath::default().area(), 0.0);
+        assert_eq!(ClosedPath::default().perimeter(), 0.0);
+        let point = ClosedPath::from_coords(&[(100, 100)]);
+        assert_eq!(point.area(), 0.0);
+        assert_eq!(point.perimeter(), 0.0);
+        let line = ClosedPath::from_coords(&[(0, 0), (100, 0), (200, 0)]);
+        assert_eq!(line.area(), 0.0);
+    }
+}
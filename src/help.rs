@@ -0,0 +1,70 @@
+// keep in sync with the key handling in draw_window_proc
+pub(crate) const KEY_BINDINGS: &[(&str, &str)] = &[
+    ("F1", "show/hide this help"),
+    ("Arrows", "move cursor (Shift: by one unit, Ctrl: by five cells)"),
+    ("Ctrl+Shift+Arrows", "pan the view"),
+    ("+ / -", "zoom in/out"),
+    ("Home", "fit the drawing into the window"),
+    ("F8", "cycle what resizing the window does"),
+    ("Space", "drop point (in cell paint mode: paint cell)"),
+    ("Ctrl+Space", "drop a control point, bending the edge into a curve"),
+    ("Shift+Space", "start a subpath, e.g. for a hole"),
+    ("I", "type in cursor coordinates (Return: go there, Shift+Return: also drop point)"),
+    ("Backspace", "remove last point"),
+    ("Return", "finish path (Shift: leave it open)"),
+    ("Escape", "discard path being drawn"),
+    ("F5", "continue drawing the selected path"),
+    ("Tab", "select next path (Shift: previous; in edit mode: vertex)"),
+    ("Ctrl+A", "select all paths"),
+    ("Delete", "delete selected paths (in edit mode: selected vertices)"),
+    ("Insert", "in edit mode: add a point halfway along the edge after the selected vertex"),
+    ("Ctrl+Z / Ctrl+Y", "undo/redo"),
+    ("H / V", "add grid column/row (Shift: remove)"),
+    ("Ctrl+H / Ctrl+V", "widen/heighten grid cells (Shift: shrink)"),
+    ("Ctrl+G", "switch between dashed and solid grid lines"),
+    ("G", "toggle snapping to the grid"),
+    ("Shift+G", "keep the cursor on the grid"),
+    ("Ctrl+Shift+G", "let the grid lines begin at the cursor (at the corner: reset)"),
+    ("N", "cycle snapping mode"),
+    ("W", "keep edges horizontal, vertical or at 45\u{B0}"),
+    (", / .", "snap to vertices and edges from less/more far away"),
+    ("B", "toggle canvas backdrop (Shift: white SVG background)"),
+    ("F3", "toggle dark mode"),
+    ("F4", "toggle antialiasing"),
+    ("O", "toggle outline preview"),
+    ("C", "toggle cell paint mode"),
+    ("F", "flood-fill cells"),
+    ("Shift+F", "switch fill rule for overlapping subpaths"),
+    ("E", "toggle vertex editing mode (arrows/numpad move selected vertices)"),
+    ("A", "toggle cursor acceleration"),
+    ("D", "duplicate paths, shifted by a cell"),
+    ("M", "add mirrored copies of paths (Shift: switch axis)"),
+    ("X", "mirror paths in place"),
+    ("R", "rotate paths clockwise (Shift: counterclockwise)"),
+    ("Numpad 2/4/6/8", "move paths (Shift: by one unit, Ctrl: by five cells)"),
+    ("Y", "toggle live symmetry"),
+    ("J", "normalize path starting points"),
+    ("Shift+J", "reverse path direction (in edit mode: of the subpath with the vertex cursor)"),
+    ("Q", "toggle path smoothing (Shift: cycle tension)"),
+    ("F2", "cycle path color (in cell paint mode: cell color; Shift: crosshair colors)"),
+    ("U", "load reference image (Shift: show/hide)"),
+    ("T", "adjust reference image (Shift: lock/unlock)"),
+    ("Ctrl+R", "import SVG as reference drawing (Shift: remove)"),
+    ("[ / ]", "reference image opacity (Shift: crosshair length)"),
+    ("Page Up/Down", "reference image size (while adjusting)"),
+    ("F6", "toggle export frame (Shift: move to cursor)"),
+    ("K", "toggle crisp SVG edges"),
+    ("F7", "toggle SVG in grid cell units"),
+    ("F9", "toggle ids on SVG shapes"),
+    ("Shift+O", "cycle SVG style"),
+    ("P", "print SVG to standard output"),
+    ("Ctrl+P", "print the drawing on paper"),
+    ("S", "save SVG (Shift: with report)"),
+    ("Ctrl+Shift+S", "save selected paths as SVG"),
+    ("Ctrl+C", "copy SVG (Shift: only the path data)"),
+    ("Ctrl+E", "export EMF"),
+    ("Ctrl+B", "export PNG"),
+    ("Ctrl+N", "new drawing"),
+    ("Ctrl+S / Ctrl+O", "save/open project (Ctrl+O also opens exported SVG)"),
+    ("L", "show/hide log"),
+];
@@ -1,36 +1,99 @@
+mod arguments;
+mod cells;
+mod clipboard;
+mod crosshair;
+mod editing;
 mod gdi_primitives;
+mod geometry;
+mod help;
+mod reference;
+mod registration;
+mod renderer;
+mod report;
+mod session;
+mod snapping;
+mod spline;
+mod transform;
+mod underlay;
+mod view;
 mod xml;
 
 
-use std::ffi::OsString;
-use std::os::windows::ffi::OsStringExt;
-use std::sync::Mutex;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ffi::{c_void, OsString};
+use std::fmt::Write as _;
+use std::ops::RangeInclusive;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
 
 use once_cell::sync::Lazy;
-use windows::core::{PWSTR, w};
-use windows::Win32::Foundation::{COLORREF, HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
+use windows::core::{PCWSTR, PWSTR, w};
+use windows::Win32::Foundation::{
+    COLORREF, GlobalFree, HINSTANCE, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM,
+};
 use windows::Win32::Graphics::Gdi::{
-    BeginPaint, COLOR_WINDOW, EndPaint, FillRect, HBRUSH, HPEN, PAINTSTRUCT, RDW_INVALIDATE,
-    RDW_UPDATENOW, RedrawWindow,
+    BeginPaint, BI_RGB, BitBlt, BITMAPINFO, BITMAPINFOHEADER, CloseEnhMetaFile, COLOR_WINDOW,
+    CreateCompatibleDC, CreateDIBSection, CreateEnhMetaFileW, DeleteDC, DeleteEnhMetaFile,
+    DeleteObject, DIB_RGB_COLORS, EndPaint, FillRect, GdiFlush, GetDeviceCaps, HBRUSH, HDC, HFONT,
+    HORZRES, HPEN, OBJ_BRUSH, OBJ_PEN, PAINTSTRUCT, PEN_STYLE, PS_ENDCAP_SQUARE, PS_JOIN_ROUND,
+    RDW_INVALIDATE, RDW_UPDATENOW, RedrawWindow, SelectObject, SetBkMode, SetTextColor, SRCCOPY,
+    TextOutW, TRANSPARENT, VERTRES,
 };
+use windows::Win32::Storage::Xps::{AbortDoc, DOCINFOW, EndDoc, EndPage, StartDocW, StartPage};
 use windows::Win32::System::LibraryLoader::GetModuleHandleW;
 use windows::Win32::System::Threading::{GetStartupInfoW, STARTUPINFOW};
-use windows::Win32::UI::Controls::Dialogs::{GetSaveFileNameW, OFN_OVERWRITEPROMPT, OPENFILENAMEW};
+use windows::Win32::UI::Controls::Dialogs::{
+    GetOpenFileNameW, GetSaveFileNameW, OFN_FILEMUSTEXIST, OFN_OVERWRITEPROMPT, OFN_PATHMUSTEXIST,
+    OPENFILENAMEW, PD_NOPAGENUMS, PD_NOSELECTION, PD_RETURNDC, PrintDlgW, PRINTDLGW,
+};
+use windows::Win32::UI::HiDpi::{
+    DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, GetDpiForWindow, SetProcessDpiAwarenessContext,
+};
 use windows::Win32::UI::Input::KeyboardAndMouse::{
-    GetKeyState, VIRTUAL_KEY, VK_BACK, VK_DOWN, VK_ESCAPE, VK_H, VK_LEFT, VK_P, VK_RETURN, VK_RIGHT,
-    VK_S, VK_SHIFT, VK_SPACE, VK_V, VK_UP,
+    GetKeyState, VIRTUAL_KEY, VK_A, VK_ADD, VK_B, VK_BACK, VK_C, VK_CONTROL, VK_D, VK_DELETE,
+    VK_DOWN, VK_E, VK_ESCAPE, VK_F, VK_F1, VK_F2, VK_F3, VK_F4, VK_F5, VK_F6, VK_F7, VK_F8, VK_F9,
+    VK_G, VK_H, VK_HOME, VK_I, VK_INSERT, VK_J, VK_K, VK_L, VK_LEFT, VK_M, VK_N, VK_NEXT,
+    VK_NUMPAD2, VK_NUMPAD4, VK_NUMPAD6, VK_NUMPAD8, VK_O, VK_OEM_4, VK_OEM_6, VK_OEM_COMMA,
+    VK_OEM_MINUS, VK_OEM_PERIOD, VK_OEM_PLUS, VK_P, VK_PRIOR, VK_Q, VK_R, VK_RETURN, VK_RIGHT, VK_S,
+    VK_SHIFT, VK_SPACE, VK_SUBTRACT, VK_T, VK_TAB, VK_U, VK_V, VK_UP, VK_W, VK_X, VK_Y, VK_Z,
 };
 use windows::Win32::UI::WindowsAndMessaging::{
-    CreateWindowExW, CW_USEDEFAULT, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageW,
-    MSG, PostQuitMessage, RegisterClassW, ShowWindow, SW_SHOWDEFAULT, TranslateMessage,
-    WINDOW_EX_STYLE, WM_CLOSE, WM_DESTROY, WM_KEYDOWN, WM_PAINT, WNDCLASSW, WS_OVERLAPPEDWINDOW,
+    CreateWindowExW, CW_USEDEFAULT, DefWindowProcW, DestroyWindow, DispatchMessageW, GetClientRect,
+    GetMessageW, IDCANCEL, IDYES, MB_ICONERROR, MB_ICONINFORMATION, MB_ICONWARNING, MB_OK,
+    MB_YESNOCANCEL, MessageBoxW, MSG, PostQuitMessage, RegisterClassW, SetWindowPos, SetWindowTextW,
+    ShowWindow, SW_SHOWDEFAULT, SWP_NOACTIVATE, SWP_NOZORDER, TranslateMessage, WINDOW_EX_STYLE,
+    WM_CHAR, WM_CLOSE, WM_DESTROY, WM_DPICHANGED, WM_KEYDOWN, WM_LBUTTONDOWN, WM_MOUSEMOVE,
+    WM_PAINT, WM_RBUTTONDOWN, WM_SIZE, WNDCLASSW, WS_OVERLAPPEDWINDOW,
 };
 
+use crate::arguments::{FileTypeChange, parse_arguments, USAGE};
+use crate::cells::flood_fill;
+use crate::clipboard::copy_text;
+use crate::crosshair::{CrosshairColors, CrosshairLength};
+use crate::editing::{
+    cycle_path, cycle_vertex, delete_selected_paths, delete_selected_vertices, duplicate_selected_paths,
+    extend_selection, insert_midpoint, move_selection, prune_selection,
+};
 use crate::gdi_primitives::{
-    begin_path, close_figure, end_path, fill_path, line_to, make_solid_brush,
-    make_solid_square_endcap_pen, move_to, rgb, select_object, stroke_path,
+    begin_path, close_figure, end_path, fill_rect_translucent, line_to, make_compatible_bitmap,
+    make_dashed_pen, make_font, make_memory_dc, make_solid_brush, make_solid_pen,
+    make_solid_square_endcap_pen, move_to, OwnedBitmap, OwnedBrush, OwnedFont, OwnedPen,
+    preserve_selection, rgb, select_object, select_object_scoped, stroke_path, take_failure,
 };
-use crate::xml::assemble_svg;
+use crate::help::KEY_BINDINGS;
+use crate::reference::load_reference;
+use crate::registration::{register_file_types, unregister_file_types};
+use crate::renderer::{AntialiasedRenderer, GdiRenderer, PathRenderer};
+use crate::report::assemble_report;
+use crate::session::{load_project, load_session, load_svg, save_project, save_session};
+use crate::snapping::{constrain_angle, round_to_multiple, snap, SnapDistance, SnapMode};
+use crate::spline::{cardinal_spline, Segment};
+use crate::transform::{rotate_paths_90, translate_paths, MirrorAxis};
+use crate::underlay::{forget_scaled_bitmap, Underlay};
+use crate::view::{ResizeMode, Zoom};
+use crate::xml::{assemble_svg, assemble_svg_subset, build_path_data, css_color, ExportFrame, SvgOptions};
 
 
 #[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -39,11 +102,531 @@ struct ApplicationState {
     pub is_drawing: bool,
     pub paths: Vec<ClosedPath>,
     pub grid_count: Point,
+    pub grid_limit: GridLimit,
+    pub canvas: CanvasConfig,
+    pub zoom: Zoom,
+    pub viewport: Point,
+    pub dpi: u32,
+    pub client_size: Point,
+    pub resize_mode: ResizeMode,
+    pub fill_canvas: bool,
+    pub solid_grid: bool,
+    pub svg_options: SvgOptions,
+    pub frame_settings: FrameSettings,
+    pub cell_paint: bool,
+
+    // for painting and flood-filling cells
+    pub cell_color: u32,
+    pub snap_mode: SnapMode,
+    pub snap_distance: SnapDistance,
+    pub crosshair_length: CrosshairLength,
+    pub crosshair_colors: CrosshairColors,
+    pub log: VecDeque<String>,
+    pub show_log: bool,
+    pub show_help: bool,
+    pub antialias: bool,
+    pub outline_preview: bool,
+    pub dark_mode: bool,
+    pub coordinate_entry: Option<String>,
+    pub edit_mode: bool,
+    pub selected_vertices: BTreeSet<(usize, usize)>,
+    pub vertex_cursor: Option<(usize, usize)>,
+    pub selected_paths: BTreeSet<usize>,
+    pub accelerate_cursor: bool,
+    pub acceleration: Acceleration,
+    pub path_limits: PathLimits,
+    pub clamp_to_grid: bool,
+    pub constrain_angles: bool,
+
+    // in GRID_ORIGIN_SUBDIVISIONS-ths of a cell from the corner of the canvas, always less than a
+    // cell, so that it stays put relative to the cells if their size changes (see grid_shift)
+    pub grid_origin: Point,
+    pub cursor_repeat: Option<KeyRepeat>,
+    pub mirror_axis: MirrorAxis,
+    pub symmetry: bool,
+    pub mirror_path_index: Option<usize>,
+    pub current_file: Option<PathBuf>,
+    pub underlay: Option<Underlay>,
+    pub adjusting_underlay: bool,
+    pub reference_paths: Vec<ClosedPath>,
+    pub undo_stack: Vec<UndoSnapshot>,
+    pub redo_stack: Vec<UndoSnapshot>,
+
+    // compared with the current paths after each action and only copied once they differ, instead
+    // of before every key press
+    pub undo_base: Vec<ClosedPath>,
+    pub dirty: bool,
+    pub session_disabled: bool,
+}
+
+impl ApplicationState {
+    // with acceleration enabled, holding down a direction key moves the cursor faster the longer it
+    // is held
+    pub fn cursor_step_multiplier(&mut self, key: VIRTUAL_KEY) -> i32 {
+        if !self.accelerate_cursor || arrow_direction(key).is_none() {
+            self.cursor_repeat = None;
+            return 1;
+        }
+
+        let now = Instant::now();
+        let count = match self.cursor_repeat {
+            Some(repeat) if repeat.key == key.0 && now - repeat.last_press <= self.acceleration.window => {
+                repeat.count + 1
+            },
+            _ => 0,
+        };
+        self.cursor_repeat = Some(KeyRepeat {
+            key: key.0,
+            last_press: now,
+            count,
+        });
+        self.acceleration.step_multiplier(count)
+    }
+
+    // in cell paint mode, paints the cell under the cursor instead
+    pub fn drop_point(&mut self) {
+        let cursor = self.cursor;
+        if self.cell_paint {
+            // paint the cell under the cursor (repainting it if it already has another color)
+            let cell_origin = cursor.cell_origin(self.canvas, self.grid_shift());
+            if cell_origin.x < 0 || cell_origin.y < 0 {
+                self.log("the cell would stick out of the canvas; move the grid origin to paint here");
+                return;
+            }
+            let cell = ClosedPath::new_cell(cell_origin, self.canvas, self.cell_color);
+            let painted = self.paths.iter_mut()
+                .find(|path| path.cell && path.points == cell.points);
+            match painted {
+                Some(path) => path.color = cell.color,
+                None => self.paths.push(cell),
+            }
+        } else {
+            let point = self.snapped_cursor();
+            if self.is_drawing && self.paths.len() == 0 {
+                // the path being drawn has vanished; don't try to continue it
+                self.log("the path being drawn no longer exists; starting a new one");
+                self.is_drawing = false;
+            }
+            if !self.is_drawing {
+                self.mirror_path_index = None;
+                if self.symmetry {
+                    // start the mirrored counterpart first so that the new path stays last
+                    self.paths.push(ClosedPath::default());
+                    self.mirror_path_index = Some(self.paths.len() - 1);
+                }
+
+                // start a new path
+                self.paths.push(ClosedPath::default());
+            }
+            // drop a point
+            if let Some(last_path) = self.paths.last_mut() {
+                last_path.points.push(point);
+            }
+
+            if let Some(mirror_path_index) = self.mirror_path_index {
+                // and its mirror image
+                let mirrored_point = point.mirrored(self.mirror_axis, self.grid_count, self.canvas);
+                if let Some(mirror_path) = self.paths.get_mut(mirror_path_index) {
+                    mirror_path.points.push(mirrored_point);
+                }
+            }
+
+            // we are certainly drawing now
+            self.is_drawing = true;
+        }
+    }
+
+    // bends the edge between the previous and the next corner into a curve; only while drawing
+    pub fn drop_control_point(&mut self) {
+        if !self.is_drawing || self.cell_paint {
+            return;
+        }
+        self.drop_point();
+        for path_index in self.drawn_path_indexes() {
+            if let Some(path) = self.paths.get_mut(path_index) {
+                if let Some(last_index) = path.points.len().checked_sub(1) {
+                    path.control_points.push(last_index);
+                }
+            }
+        }
+    }
+
+    // also for the mirror image, if any
+    pub fn start_subpath(&mut self) {
+        if !self.is_drawing {
+            return;
+        }
+        for path_index in self.drawn_path_indexes() {
+            if let Some(path) = self.paths.get_mut(path_index) {
+                if !path.subpath_pending() {
+                    path.subpath_starts.push(path.points.len());
+                }
+            }
+        }
+    }
+
+    // either may refer to a path that no longer exists
+    fn drawn_path_indexes(&self) -> Vec<usize> {
+        let mut path_indexes: Vec<usize> = self.paths.len().checked_sub(1).into_iter().collect();
+        path_indexes.extend(self.mirror_path_index);
+        path_indexes
+    }
+
+    // the path being drawn is always the last one, so the path is moved to the end of the list,
+    // which also brings it in front of the others
+    pub fn resume_path(&mut self) {
+        if self.is_drawing {
+            self.log("finish the current path before continuing another one");
+            return;
+        }
+        let path_index = match self.selected_path_index() {
+            Some(path_index) => path_index,
+            None => {
+                self.log("no path selected");
+                return;
+            },
+        };
+        if self.paths[path_index].cell {
+            self.log("painted cells cannot be continued");
+            return;
+        }
+
+        let path = self.paths.remove(path_index);
+        self.paths.push(path);
+        self.selected_paths.clear();
+        self.edit_mode = false;
+        self.selected_vertices.clear();
+        self.vertex_cursor = None;
+        self.mirror_path_index = None;
+        self.is_drawing = true;
+        self.log(format!("continuing path {}", path_index));
+    }
+
+    pub fn finish_path(&mut self) {
+        self.is_drawing = false;
+        self.mirror_path_index = None;
+    }
+
+    pub fn finish_open_path(&mut self) {
+        if self.is_drawing {
+            if let Some(path) = self.paths.last_mut() {
+                path.closed = false;
+            }
+            if let Some(path) = self.mirror_path_index.and_then(|i| self.paths.get_mut(i)) {
+                path.closed = false;
+            }
+        }
+        self.finish_path();
+    }
+
+    pub fn undo_snapshot(&self) -> UndoSnapshot {
+        UndoSnapshot {
+            paths: self.paths.clone(),
+            is_drawing: self.is_drawing,
+            cursor: self.cursor,
+            mirror_path_index: self.mirror_path_index,
+        }
+    }
+
+    // the paths themselves are compared later, see undo_base
+    pub fn undo_marker(&self) -> UndoMarker {
+        UndoMarker {
+            is_drawing: self.is_drawing,
+            cursor: self.cursor,
+            mirror_path_index: self.mirror_path_index,
+        }
+    }
+
+    // e.g. after a different drawing has been loaded
+    pub fn reset_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.undo_base = self.paths.clone();
+    }
+
+    fn restore_snapshot(&mut self, snapshot: UndoSnapshot) {
+        self.paths = snapshot.paths;
+        self.is_drawing = snapshot.is_drawing;
+        self.cursor = snapshot.cursor;
+        self.mirror_path_index = snapshot.mirror_path_index;
+        self.undo_base = self.paths.clone();
+        self.dirty = true;
+    }
+
+    // only records anything if the action changed the drawing
+    pub fn record_undo(&mut self, before: UndoMarker) {
+        let paths_changed = self.undo_base != self.paths;
+        if !paths_changed && before.is_drawing == self.is_drawing {
+            // cursor movements and mode changes are not undoable on their own
+            return;
+        }
+        if paths_changed {
+            self.dirty = true;
+        }
+        if self.undo_stack.len() >= UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        let paths = std::mem::replace(&mut self.undo_base, self.paths.clone());
+        self.undo_stack.push(UndoSnapshot {
+            paths,
+            is_drawing: before.is_drawing,
+            cursor: before.cursor,
+            mirror_path_index: before.mirror_path_index,
+        });
+        self.redo_stack.clear();
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.undo_stack.pop() {
+            let current = self.undo_snapshot();
+            self.redo_stack.push(current);
+            self.restore_snapshot(snapshot);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.redo_stack.pop() {
+            let current = self.undo_snapshot();
+            self.undo_stack.push(current);
+            self.restore_snapshot(snapshot);
+        }
+    }
+
+    // in drawing coordinates, always less than a cell; cell (0, 0) begins there, and snapping and
+    // the coordinates shown and typed in are relative to it, but the canvas and the exported area
+    // stay put
+    pub fn grid_shift(&self) -> Point {
+        let to_drawing = |origin: i32, factor: i32| {
+            let fraction = i64::from(origin.rem_euclid(GRID_ORIGIN_SUBDIVISIONS));
+            let subdivisions = i64::from(GRID_ORIGIN_SUBDIVISIONS);
+            let shift = (fraction * i64::from(factor) + subdivisions / 2) / subdivisions;
+            // rounding may reach the next grid line
+            i32::try_from(shift).unwrap().rem_euclid(factor)
+        };
+        Point {
+            x: to_drawing(self.grid_origin.x, self.canvas.horizontal_factor),
+            y: to_drawing(self.grid_origin.y, self.canvas.vertical_factor),
+        }
+    }
+
+    // rounded to the precision of grid_origin
+    pub fn set_grid_origin(&mut self, point: Point) {
+        let to_cells = |value: i32, factor: i32| {
+            let subdivisions = i64::from(GRID_ORIGIN_SUBDIVISIONS);
+            let within_cell = i64::from(value.rem_euclid(factor));
+            let origin = (within_cell * subdivisions + i64::from(factor) / 2) / i64::from(factor);
+            i32::try_from(origin).unwrap().rem_euclid(GRID_ORIGIN_SUBDIVISIONS)
+        };
+        self.grid_origin = Point {
+            x: to_cells(point.x, self.canvas.horizontal_factor),
+            y: to_cells(point.y, self.canvas.vertical_factor),
+        };
+    }
+
+    pub fn grid_extent(&self) -> Point {
+        Point {
+            x: self.grid_count.x * self.canvas.horizontal_factor,
+            y: self.grid_count.y * self.canvas.vertical_factor,
+        }
+    }
+
+    pub fn clamp_cursor(&mut self) {
+        if self.clamp_to_grid {
+            let extent = self.grid_extent();
+            self.cursor.x = self.cursor.x.min(extent.x);
+            self.cursor.y = self.cursor.y.min(extent.y);
+        }
+    }
+
+    // if the input is not two non-negative integers, the entry stays open so it can be corrected
+    pub fn confirm_coordinate_entry(&mut self, drop_point: bool) {
+        let entry = match self.coordinate_entry.take() {
+            Some(e) => e,
+            None => return,
+        };
+        let numbers: Vec<Option<i32>> = entry
+            .split([' ', ','])
+            .filter(|piece| piece.len() > 0)
+            .map(|piece| piece.parse().ok().filter(|&number: &i32| number >= 0))
+            .collect();
+        if let [Some(x), Some(y)] = numbers[..] {
+            // the coordinates are relative to the grid origin, like those in the title bar
+            let grid_shift = self.grid_shift();
+            self.cursor = Point { x: grid_shift.x + x, y: grid_shift.y + y };
+            self.log(format!("moved the cursor to ({}, {})", x, y));
+            if drop_point {
+                self.drop_point();
+            }
+        } else {
+            self.log(format!("{:?} is not a pair of non-negative coordinates", entry));
+            self.coordinate_entry = Some(entry);
+        }
+    }
+
+    // e.g. after the grid has been shrunk
+    pub fn warn_about_points_outside_grid(&mut self) {
+        let extent = self.grid_extent();
+        let outside = self.paths.iter()
+            .flat_map(|path| &path.points)
+            .any(|point| point.x > extent.x || point.y > extent.y);
+        if outside {
+            self.log("some points now lie outside the grid");
+        }
+    }
+
+    // the oldest messages are forgotten once the log is full
+    pub fn log<S: Into<String>>(&mut self, message: S) {
+        while self.log.len() >= LOG_CAPACITY {
+            self.log.pop_front();
+        }
+        self.log.push_back(message.into());
+    }
+
+    // in edit mode, the path of the most recently selected vertex; otherwise the last selected path
+    // or, failing that, the most recently drawn non-empty path
+    pub fn selected_path_index(&self) -> Option<usize> {
+        if self.edit_mode {
+            self.vertex_cursor.map(|(path_index, _point_index)| path_index)
+        } else {
+            self.selected_paths.iter().next_back().copied()
+                .or_else(|| self.paths.iter().rposition(|path| path.points.len() > 0))
+        }
+    }
+
+    // all selected paths or, if none are selected or in edit mode, selected_path_index
+    pub fn selected_path_indexes(&self) -> Vec<usize> {
+        if !self.edit_mode && self.selected_paths.len() > 0 {
+            self.selected_paths.iter().copied().collect()
+        } else {
+            self.selected_path_index().into_iter().collect()
+        }
+    }
+
+    // includes the DPI of the display; exports only use the zoom level so that they look the same
+    // everywhere
+    pub fn screen_zoom(&self) -> Zoom {
+        let dpi: i32 = self.effective_dpi().try_into().unwrap();
+        let default_dpi: i32 = DEFAULT_DPI.try_into().unwrap();
+        Zoom {
+            numerator: self.zoom.numerator * dpi,
+            denominator: self.zoom.denominator * default_dpi,
+        }
+    }
+
+    // the standard DPI if unknown
+    pub fn effective_dpi(&self) -> u32 {
+        if self.dpi == 0 { DEFAULT_DPI } else { self.dpi }
+    }
+
+    // with `fit`, the zoom is also adjusted so that the region fills the window, leaving `margin`
+    // (pixels at the standard DPI) around it
+    pub fn frame_region(&mut self, min: Point, max: Point, margin: i32, fit: bool) {
+        if self.client_size.x <= 0 || self.client_size.y <= 0 {
+            // minimized or not yet shown
+            return;
+        }
+        if fit {
+            let available = Point {
+                x: dpi_scale_inverse(self.client_size.x, self.effective_dpi()) - 2*margin,
+                y: dpi_scale_inverse(self.client_size.y, self.effective_dpi()) - 2*margin,
+            };
+            let content = Point { x: max.x - min.x, y: max.y - min.y };
+            self.zoom = Zoom::fitting(content, available);
+        }
+        let zoom = self.screen_zoom();
+        self.viewport = Point {
+            x: self.canvas.left_offset + (min.x + max.x) / 2 - zoom.unscale(self.client_size.x / 2),
+            y: self.canvas.top_offset + (min.y + max.y) / 2 - zoom.unscale(self.client_size.y / 2),
+        };
+    }
+
+    pub fn apply_resize_mode(&mut self) {
+        if self.grid_count.x == 0 || self.grid_count.y == 0 {
+            return;
+        }
+        let extent = self.grid_extent();
+        match self.resize_mode {
+            ResizeMode::Anchor => {},
+            ResizeMode::Center => self.frame_region(Point::default(), extent, 0, false),
+            ResizeMode::Fit => self.frame_region(Point::default(), extent, FIT_MARGIN, true),
+        }
+    }
+
+    pub fn snapped_cursor(&self) -> Point {
+        let grid_shift = self.grid_shift();
+        let point = snap(
+            self.snap_mode, self.snap_distance, self.cursor, &self.paths, self.grid_count, self.canvas,
+            grid_shift,
+        );
+        let previous_point = self.paths.last()
+            .filter(|path| self.is_drawing && self.constrain_angles && !path.subpath_pending())
+            .and_then(|path| path.points.last());
+        // stay on the grid if the point was snapped to it
+        let on_grid = matches!(self.snap_mode, SnapMode::Grid | SnapMode::All)
+            && (point.x - grid_shift.x).rem_euclid(self.canvas.horizontal_factor) == 0
+            && (point.y - grid_shift.y).rem_euclid(self.canvas.vertical_factor) == 0;
+        let step = if on_grid {
+            Point { x: self.canvas.horizontal_factor, y: self.canvas.vertical_factor }
+        } else {
+            Point { x: 1, y: 1 }
+        };
+        match previous_point {
+            // the angle takes precedence over the other kinds of snapping
+            Some(&previous_point) => constrain_angle(previous_point, point, step),
+            None => point,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct UndoSnapshot {
+    pub paths: Vec<ClosedPath>,
+    pub is_drawing: bool,
+    pub cursor: Point,
+    pub mirror_path_index: Option<usize>,
+}
+
+// taken before each action
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct UndoMarker {
+    pub is_drawing: bool,
+    pub cursor: Point,
+    pub mirror_path_index: Option<usize>,
+}
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 struct ClosedPath {
     pub points: Vec<Point>,
+    pub cell: bool,
+    pub smooth: bool,
+    pub tension_percent: i32,
+
+    // open paths are outlined, not filled
+    pub closed: bool,
+    pub color: u32,
+
+    // ascending; the first subpath always begins at the first point and is not listed
+    pub subpath_starts: Vec<usize>,
+    pub control_points: Vec<usize>,
+
+    // used as the `id` of the exported shape instead of a numbered one
+    pub label: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct CanvasConfig {
+    // in drawing coordinates
+    pub left_offset: i32,
+    pub top_offset: i32,
+    pub horizontal_factor: i32,
+    pub vertical_factor: i32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct KeyRepeat {
+    pub key: u16,
+    pub last_press: Instant,
+    pub count: i32,
 }
 
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -52,24 +635,422 @@ struct Point {
     pub y: i32,
 }
 
+// beyond this, painting the grid lines would make the window unresponsive
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct GridLimit(pub Point);
+impl GridLimit {
+    pub fn clamped(limit: Point) -> Self {
+        Self(Point {
+            x: limit.x.clamp(1, MAX_GRID_LIMIT.x),
+            y: limit.y.clamp(1, MAX_GRID_LIMIT.y),
+        })
+    }
+
+    // negative sizes become zero
+    pub fn apply(&self, grid_count: Point) -> Point {
+        Point {
+            x: grid_count.x.clamp(0, self.0.x),
+            y: grid_count.y.clamp(0, self.0.y),
+        }
+    }
+}
+impl Default for GridLimit {
+    fn default() -> Self {
+        Self(DEFAULT_GRID_LIMIT)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Acceleration {
+    // the longest time between two presses that still counts as holding the key down
+    pub window: Duration,
+    pub presses_per_step: i32,
+    pub max_steps: i32,
+}
+impl Acceleration {
+    pub fn clamped(window: Duration, presses_per_step: i32, max_steps: i32) -> Self {
+        Self {
+            window: window.clamp(Duration::from_millis(1), MAX_ACCELERATION_WINDOW),
+            presses_per_step: presses_per_step.clamp(1, MAX_ACCELERATION_SETTING),
+            max_steps: max_steps.clamp(1, MAX_ACCELERATION_SETTING),
+        }
+    }
+
+    pub fn step_multiplier(&self, repeat_count: i32) -> i32 {
+        (1 + repeat_count / self.presses_per_step).min(self.max_steps)
+    }
+}
+impl Default for Acceleration {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_ACCELERATION_WINDOW,
+            presses_per_step: DEFAULT_ACCELERATION_PRESSES_PER_STEP,
+            max_steps: DEFAULT_MAX_ACCELERATION,
+        }
+    }
+}
+
+// how many paths a document may have before rendering it becomes slow
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct PathLimits {
+    pub warning: usize,
+    pub hard_limit: Option<usize>,
+}
+impl Default for PathLimits {
+    fn default() -> Self {
+        Self {
+            warning: DEFAULT_PATH_COUNT_WARNING,
+            hard_limit: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct FrameSettings {
+    pub aspect: Point,
+
+    // the height follows from the aspect ratio
+    pub output_width: i32,
+}
+impl FrameSettings {
+    pub fn clamped(aspect: Point, output_width: i32) -> Self {
+        Self {
+            aspect: Point {
+                x: aspect.x.clamp(1, MAX_EXPORT_FRAME_SIDE),
+                y: aspect.y.clamp(1, MAX_EXPORT_FRAME_SIDE),
+            },
+            output_width: output_width.clamp(1, MAX_EXPORT_FRAME_SIDE),
+        }
+    }
+
+    pub fn height(&self, width: i32) -> i32 {
+        let height = i64::from(width) * i64::from(self.aspect.y) / i64::from(self.aspect.x);
+        height.clamp(0, i64::from(i32::MAX)).try_into().unwrap()
+    }
+
+    pub fn output_size(&self) -> Point {
+        Point {
+            x: self.output_width,
+            y: self.height(self.output_width),
+        }
+    }
+}
+impl Default for FrameSettings {
+    fn default() -> Self {
+        Self {
+            aspect: DEFAULT_EXPORT_FRAME_ASPECT,
+            output_width: DEFAULT_EXPORT_FRAME_OUTPUT_WIDTH,
+        }
+    }
+}
+
+impl Default for CanvasConfig {
+    fn default() -> Self {
+        Self {
+            left_offset: DEFAULT_LEFT_OFFSET,
+            top_offset: DEFAULT_TOP_OFFSET,
+            horizontal_factor: DEFAULT_HORIZONTAL_FACTOR,
+            vertical_factor: DEFAULT_VERTICAL_FACTOR,
+        }
+    }
+}
+
+impl CanvasConfig {
+    // the given point of the drawing appears where the origin would otherwise be
+    pub fn panned(&self, viewport: Point) -> CanvasConfig {
+        CanvasConfig {
+            left_offset: self.left_offset - viewport.x,
+            top_offset: self.top_offset - viewport.y,
+            ..*self
+        }
+    }
+}
+
+impl Default for ClosedPath {
+    fn default() -> Self {
+        Self {
+            points: Vec::new(),
+            cell: false,
+            smooth: false,
+            tension_percent: 0,
+            closed: true,
+            color: BLACK.0,
+            subpath_starts: Vec::new(),
+            control_points: Vec::new(),
+            label: None,
+        }
+    }
+}
+
+impl ClosedPath {
+    pub fn new_cell(top_left: Point, canvas: CanvasConfig, color: u32) -> Self {
+        let bottom_right = Point {
+            x: top_left.x + canvas.horizontal_factor,
+            y: top_left.y + canvas.vertical_factor,
+        };
+        Self {
+            points: vec![
+                top_left,
+                Point { x: bottom_right.x, y: top_left.y },
+                bottom_right,
+                Point { x: top_left.x, y: bottom_right.y },
+            ],
+            cell: true,
+            color,
+            ..Self::default()
+        }
+    }
+
+    // a subpath that would begin beyond the remaining points is removed as well
+    pub fn pop_point(&mut self) {
+        self.points.pop();
+        let point_count = self.points.len();
+        self.subpath_starts.retain(|&start| start < point_count);
+        self.control_points.retain(|&index| index < point_count);
+    }
+
+    // inserting at the start of a subpath appends to the previous one
+    pub fn insert_point(&mut self, index: usize, point: Point) {
+        self.points.insert(index, point);
+        for start in &mut self.subpath_starts {
+            if *start >= index {
+                *start += 1;
+            }
+        }
+        for control_index in &mut self.control_points {
+            if *control_index >= index {
+                *control_index += 1;
+            }
+        }
+    }
+
+    // a subpath left without points disappears
+    pub fn remove_point(&mut self, index: usize) {
+        self.points.remove(index);
+        self.control_points.retain(|&control_index| control_index != index);
+        for control_index in &mut self.control_points {
+            if *control_index > index {
+                *control_index -= 1;
+            }
+        }
+        for start in &mut self.subpath_starts {
+            if *start > index {
+                *start -= 1;
+            }
+        }
+        let point_count = self.points.len();
+        self.subpath_starts.dedup();
+        self.subpath_starts.retain(|&start| start > 0 && start < point_count);
+    }
+
+    pub fn subpath_pending(&self) -> bool {
+        self.subpath_starts.last() == Some(&self.points.len())
+    }
+}
+
+#[cfg(test)]
+impl ClosedPath {
+    pub fn from_coords(coordinates: &[(i32, i32)]) -> Self {
+        Self {
+            points: coordinates.iter().map(|&(x, y)| Point { x, y }).collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl Point {
+    pub fn cell_origin(&self, canvas: CanvasConfig, grid_shift: Point) -> Point {
+        Point {
+            x: (self.x - grid_shift.x).div_euclid(canvas.horizontal_factor) * canvas.horizontal_factor + grid_shift.x,
+            y: (self.y - grid_shift.y).div_euclid(canvas.vertical_factor) * canvas.vertical_factor + grid_shift.y,
+        }
+    }
+}
+
 
-const LEFT_OFFSET: i32 = 100;
-const TOP_OFFSET: i32 = 100;
-const HORIZONTAL_FACTOR: i32 = 100;
-const VERTICAL_FACTOR: i32 = 100;
-const CROSSHAIR_LENGTH: i32 = 20;
+const DEFAULT_LEFT_OFFSET: i32 = 100;
+const DEFAULT_TOP_OFFSET: i32 = 100;
+const DEFAULT_HORIZONTAL_FACTOR: i32 = 100;
+const DEFAULT_VERTICAL_FACTOR: i32 = 100;
+const GRID_FACTOR_STEP: i32 = 10;
+const MIN_GRID_FACTOR: i32 = 10;
+// keeps the size of the largest grid at the highest zoom level and DPI within an i32
+const MAX_GRID_FACTOR: i32 = 500;
+const DEFAULT_GRID_LIMIT: Point = Point { x: 100, y: 100 };
+const MAX_GRID_LIMIT: Point = Point { x: 1000, y: 1000 };
+const GRID_ORIGIN_SUBDIVISIONS: i32 = 1000;
+const DEFAULT_DPI: u32 = 96;
 const CROSSHAIR_THICKNESS: u32 = 4;
-const RENDER_NUMERATOR: i32 = 1;
-const RENDER_DENOMINATOR: i32 = 2;
-const NOT_DRAWING_CROSSHAIR_COLOR: COLORREF = rgb(0x00, 0x00, 0xFF);
-const DRAWING_CROSSHAIR_COLOR: COLORREF = rgb(0xFF, 0x00, 0x00);
+const CROSSHAIR_END_CAP: PEN_STYLE = PS_ENDCAP_SQUARE;
+const CROSSHAIR_JOIN: PEN_STYLE = PS_JOIN_ROUND;
 const BLACK: COLORREF = rgb(0x00, 0x00, 0x00);
+const WHITE: COLORREF = rgb(0xFF, 0xFF, 0xFF);
+const GRID_COLOR: COLORREF = rgb(0x80, 0x80, 0x80);
+const GRID_DASHES: [u32; 2] = [4, 4];
+const CANVAS_COLOR: COLORREF = rgb(0xF4, 0xF4, 0xEC);
+const DARK_BACKGROUND_COLOR: COLORREF = rgb(0x1E, 0x1E, 0x1E);
+const DARK_CANVAS_COLOR: COLORREF = rgb(0x2A, 0x2A, 0x30);
+const DARK_GRID_COLOR: COLORREF = rgb(0x70, 0x70, 0x70);
+const DARK_SOLID_GRID_COLOR: COLORREF = rgb(0xC0, 0xC0, 0xC0);
+const DARK_MODE_PATH_COLOR: COLORREF = rgb(0xE8, 0xE8, 0xE8);
+const SNAP_COLOR: COLORREF = rgb(0x00, 0xA0, 0x00);
+const SNAP_MARKER_SIZE: i32 = 24;
+const LOG_CAPACITY: usize = 256;
+const LOG_VISIBLE_LINES: usize = 12;
+const LOG_LINE_HEIGHT: i32 = 16;
+const LOG_PANEL_WIDTH: i32 = 640;
+const LOG_MARGIN: i32 = 8;
+const LOG_BACKGROUND_COLOR: COLORREF = rgb(0x30, 0x30, 0x30);
+const LOG_TEXT_COLOR: COLORREF = rgb(0xF0, 0xF0, 0xF0);
+const LOG_FONT_HEIGHT: i32 = 12;
+const LOG_FONT_FACE: &str = "Segoe UI";
+const HELP_LEFT: i32 = 2*LOG_MARGIN + LOG_PANEL_WIDTH;
+const HELP_PANEL_WIDTH: i32 = 560;
+const HELP_KEY_COLUMN_WIDTH: i32 = 150;
+const HELP_OPACITY: u8 = 0xD0;
+const SELECTION_COLOR: COLORREF = rgb(0xFF, 0x80, 0x00);
+const INTERSECTION_COLOR: COLORREF = rgb(0xFF, 0x00, 0x40);
+const VERTEX_MARKER_SIZE: i32 = 16;
+const LAST_POINT_COLOR: COLORREF = rgb(0x00, 0x80, 0xFF);
+const LAST_POINT_MARKER_SIZE: i32 = 20;
+const CONTROL_POINT_COLOR: COLORREF = rgb(0xA0, 0x00, 0xC0);
+const CONTROL_POINT_MARKER_SIZE: i32 = 12;
+const CLOSING_PREVIEW_COLOR: COLORREF = rgb(0x40, 0x40, 0x40);
+const CLOSING_PREVIEW_DASHES: [u32; 2] = [6, 4];
+const OPEN_PATH_THICKNESS: u32 = 3;
+const DRAWING_FILL_OPACITY: u8 = 0x80;
+const PATH_PALETTE: [COLORREF; 8] = [
+    BLACK,
+    rgb(0x80, 0x80, 0x80),
+    rgb(0xC0, 0x00, 0x00),
+    rgb(0xE0, 0x80, 0x00),
+    rgb(0xE0, 0xC0, 0x00),
+    rgb(0x00, 0x90, 0x00),
+    rgb(0x00, 0x40, 0xC0),
+    rgb(0x80, 0x00, 0xA0),
+];
+const SYMMETRY_AXIS_COLOR: COLORREF = rgb(0xC0, 0x00, 0xC0);
+const DEFAULT_ACCELERATION_WINDOW: Duration = Duration::from_millis(200);
+const DEFAULT_ACCELERATION_PRESSES_PER_STEP: i32 = 5;
+const DEFAULT_MAX_ACCELERATION: i32 = 8;
+const MAX_ACCELERATION_WINDOW: Duration = Duration::from_secs(2);
+const MAX_ACCELERATION_SETTING: i32 = 100;
+const COARSE_STEP_CELLS: i32 = 5;
+const PAN_STEP: i32 = 100;
+const FIT_MARGIN: i32 = 20;
+const DEFAULT_PATH_COUNT_WARNING: usize = 1000;
+const UNDERLAY_OPACITY_STEP: u8 = 0x20;
+const UNDERLAY_SCALE_STEP: i32 = 10;
+const SPLINE_TENSIONS_PERCENT: [i32; 4] = [0, 25, 50, 75];
+const UNDO_LIMIT: usize = 256;
+const DEFAULT_EXPORT_FRAME_ASPECT: Point = Point { x: 16, y: 9 };
+const DEFAULT_EXPORT_FRAME_OUTPUT_WIDTH: i32 = 1920;
+const MAX_EXPORT_FRAME_SIDE: i32 = 16384;
+const EXPORT_FRAME_DEFAULT_CELLS: i32 = 16;
+const EXPORT_FRAME_COLOR: COLORREF = rgb(0x00, 0x80, 0x80);
+const REFERENCE_PATH_COLOR: COLORREF = rgb(0xC8, 0xC8, 0xC8);
+const PROJECT_FILTER: PCWSTR = w!("PixelPath Projects (*.pixelpath)\0*.pixelpath\0All Files (*.*)\0*.*\0\0");
+const OPEN_FILTER: PCWSTR = w!("PixelPath Projects and SVG Files (*.pixelpath;*.svg)\0*.pixelpath;*.svg\0All Files (*.*)\0*.*\0\0");
 
 static STATE: Lazy<Mutex<ApplicationState>> = Lazy::new(|| Mutex::new(ApplicationState::default()));
-static DRAWING_CROSSHAIR_PEN: Lazy<HPEN> = Lazy::new(|| make_solid_square_endcap_pen(CROSSHAIR_THICKNESS, DRAWING_CROSSHAIR_COLOR));
-static NOT_DRAWING_CROSSHAIR_PEN: Lazy<HPEN> = Lazy::new(|| make_solid_square_endcap_pen(CROSSHAIR_THICKNESS, NOT_DRAWING_CROSSHAIR_COLOR));
-static GRID_PEN: Lazy<HPEN> = Lazy::new(|| make_solid_square_endcap_pen(1, BLACK));
-static FONT_BRUSH: Lazy<HBRUSH> = Lazy::new(|| make_solid_brush(BLACK));
+static SNAP_PEN: Lazy<OwnedPen> = Lazy::new(|| make_solid_square_endcap_pen(2, SNAP_COLOR));
+static SYMMETRY_AXIS_PEN: Lazy<OwnedPen> = Lazy::new(|| make_solid_square_endcap_pen(2, SYMMETRY_AXIS_COLOR));
+static EXPORT_FRAME_PEN: Lazy<OwnedPen> = Lazy::new(|| make_solid_square_endcap_pen(2, EXPORT_FRAME_COLOR));
+static CLOSING_PREVIEW_PEN: Lazy<OwnedPen> = Lazy::new(|| make_dashed_pen(1, CLOSING_PREVIEW_COLOR, &CLOSING_PREVIEW_DASHES));
+static GRID_PEN: Lazy<OwnedPen> = Lazy::new(|| make_dashed_pen(1, GRID_COLOR, &GRID_DASHES));
+static SOLID_GRID_PEN: Lazy<OwnedPen> = Lazy::new(|| make_solid_square_endcap_pen(1, BLACK));
+static DARK_GRID_PEN: Lazy<OwnedPen> = Lazy::new(|| make_dashed_pen(1, DARK_GRID_COLOR, &GRID_DASHES));
+static DARK_SOLID_GRID_PEN: Lazy<OwnedPen> = Lazy::new(|| make_solid_square_endcap_pen(1, DARK_SOLID_GRID_COLOR));
+static PATH_BRUSHES: Lazy<Mutex<BTreeMap<u32, OwnedBrush>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+static PATH_PENS: Lazy<Mutex<BTreeMap<u32, OwnedPen>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+static CROSSHAIR_PENS: Lazy<Mutex<BTreeMap<u32, OwnedPen>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+static LOG_FONTS: Lazy<Mutex<BTreeMap<u32, OwnedFont>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+static BACK_BUFFER: Lazy<Mutex<Option<(Point, OwnedBitmap)>>> = Lazy::new(|| Mutex::new(None));
+static SELF_INTERSECTIONS: Lazy<Mutex<Vec<(ClosedPath, bool)>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static LAST_SAVE_DIRECTORY: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+static EXPORT_BACKGROUND_BRUSH: Lazy<OwnedBrush> = Lazy::new(|| make_solid_brush(WHITE));
+static CANVAS_BRUSH: Lazy<OwnedBrush> = Lazy::new(|| make_solid_brush(CANVAS_COLOR));
+static DARK_BACKGROUND_BRUSH: Lazy<OwnedBrush> = Lazy::new(|| make_solid_brush(DARK_BACKGROUND_COLOR));
+static DARK_CANVAS_BRUSH: Lazy<OwnedBrush> = Lazy::new(|| make_solid_brush(DARK_CANVAS_COLOR));
+static LOG_BACKGROUND_BRUSH: Lazy<OwnedBrush> = Lazy::new(|| make_solid_brush(LOG_BACKGROUND_COLOR));
+static SELECTION_BRUSH: Lazy<OwnedBrush> = Lazy::new(|| make_solid_brush(SELECTION_COLOR));
+static LAST_POINT_BRUSH: Lazy<OwnedBrush> = Lazy::new(|| make_solid_brush(LAST_POINT_COLOR));
+static CONTROL_POINT_BRUSH: Lazy<OwnedBrush> = Lazy::new(|| make_solid_brush(CONTROL_POINT_COLOR));
+
+
+fn path_brush(color: u32) -> HBRUSH {
+    let mut brushes = PATH_BRUSHES.lock().unwrap_or_else(PoisonError::into_inner);
+    brushes.entry(color)
+        .or_insert_with(|| make_solid_brush(COLORREF(color)))
+        .handle()
+}
+
+
+// the check is quadratic in the number of edges and needed on every repaint, so it is only
+// repeated for the paths that have changed since the last call
+fn self_intersecting_paths(paths: &[ClosedPath]) -> Vec<bool> {
+    let mut cache = SELF_INTERSECTIONS.lock().unwrap_or_else(PoisonError::into_inner);
+    cache.truncate(paths.len());
+    for (path_index, path) in paths.iter().enumerate() {
+        match cache.get_mut(path_index) {
+            Some((cached_path, _intersects)) if cached_path == path => {},
+            Some(entry) => *entry = (path.clone(), path.closed && path.self_intersects()),
+            None => cache.push((path.clone(), path.closed && path.self_intersects())),
+        }
+    }
+    cache.iter()
+        .map(|(_path, intersects)| *intersects)
+        .collect()
+}
+
+
+fn crosshair_pen(color: COLORREF) -> HPEN {
+    let mut pens = CROSSHAIR_PENS.lock().unwrap_or_else(PoisonError::into_inner);
+    pens.entry(color.0)
+        .or_insert_with(|| make_solid_pen(CROSSHAIR_THICKNESS, color, CROSSHAIR_END_CAP, CROSSHAIR_JOIN))
+        .handle()
+}
+
+
+fn path_pen(color: u32) -> HPEN {
+    let mut pens = PATH_PENS.lock().unwrap_or_else(PoisonError::into_inner);
+    pens.entry(color)
+        .or_insert_with(|| make_solid_square_endcap_pen(OPEN_PATH_THICKNESS, COLORREF(color)))
+        .handle()
+}
+
+
+fn log_font(dpi: u32) -> HFONT {
+    let mut fonts = LOG_FONTS.lock().unwrap_or_else(PoisonError::into_inner);
+    fonts.entry(dpi)
+        .or_insert_with(|| make_font(dpi_scale(LOG_FONT_HEIGHT, dpi), LOG_FONT_FACE))
+        .handle()
+}
+
+
+fn dpi_scale(value: i32, dpi: u32) -> i32 {
+    let dpi: i32 = dpi.try_into().unwrap();
+    let default_dpi: i32 = DEFAULT_DPI.try_into().unwrap();
+    value * dpi / default_dpi
+}
+
+
+fn dpi_scale_inverse(value: i32, dpi: u32) -> i32 {
+    let dpi: i32 = dpi.try_into().unwrap();
+    let default_dpi: i32 = DEFAULT_DPI.try_into().unwrap();
+    value * default_dpi / dpi
+}
+
+
+// a lock poisoned by a panic is recovered, with a warning in the log
+fn lock_state() -> MutexGuard<'static, ApplicationState> {
+    STATE.lock().unwrap_or_else(|poisoned| {
+        STATE.clear_poison();
+        let mut state_guard = poisoned.into_inner();
+        state_guard.log("warning: recovered from an internal error; the drawing may be inconsistent");
+        state_guard
+    })
+}
 
 
 fn default_window_proc(handle: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
@@ -77,13 +1058,99 @@ fn default_window_proc(handle: HWND, message: u32, wparam: WPARAM, lparam: LPARA
 }
 
 unsafe extern "system" fn draw_window_proc(handle: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    // unwinding into Windows is undefined behavior; give up on the message instead
+    let handled = std::panic::catch_unwind(|| handle_draw_window_message(handle, message, wparam, lparam));
+    match handled {
+        Ok(result) => result,
+        Err(payload) => {
+            let message_text = format!(
+                "internal error ({}); the last action may not have been completed",
+                panic_message(payload.as_ref()),
+            );
+            lock_state().log(message_text);
+            default_window_proc(handle, message, wparam, lparam)
+        },
+    }
+}
+
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "unknown panic"
+    }
+}
+
+
+// locks held during a panic become poisoned; they are used anyway afterwards, as the window would
+// become unusable otherwise
+fn handle_draw_window_message(handle: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     if message == WM_CLOSE {
+        if !may_discard_changes(handle, w!("The drawing has unsaved changes. Save them as a project before closing?")) {
+            // don't close
+            return LRESULT(0);
+        }
         unsafe { DestroyWindow(handle) }
             .expect("failed to destroy window");
     } else if message == WM_DESTROY {
+        let state_guard = lock_state();
+        let saved = if state_guard.session_disabled { Ok(()) } else { save_session(&state_guard) };
+        drop(state_guard);
+        if let Err(e) = saved {
+            // the window (and with it the log) is going away
+            show_error(HWND::default(), &format!("error saving session: {}", e));
+        }
         unsafe { PostQuitMessage(0) };
+    } else if message == WM_DPICHANGED {
+        // the window has moved to a display with a different scale factor
+        let dpi = u32::from((wparam.0 & 0xFFFF) as u16);
+        lock_state().dpi = dpi;
+        let suggested = unsafe { &*(lparam.0 as *const RECT) };
+        let _ = unsafe {
+            SetWindowPos(
+                handle,
+                None,
+                suggested.left,
+                suggested.top,
+                suggested.right - suggested.left,
+                suggested.bottom - suggested.top,
+                SWP_NOZORDER | SWP_NOACTIVATE,
+            )
+        };
+        unsafe { RedrawWindow(handle, None, None, RDW_INVALIDATE | RDW_UPDATENOW) };
+        return LRESULT(0);
+    } else if message == WM_SIZE {
+        {
+            let mut state_guard = lock_state();
+            state_guard.client_size = Point {
+                x: i32::from((lparam.0 & 0xFFFF) as u16),
+                y: i32::from(((lparam.0 >> 16) & 0xFFFF) as u16),
+            };
+            state_guard.apply_resize_mode();
+        }
+        // the back buffer no longer fits; the next paint creates a new one
+        *BACK_BUFFER.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        unsafe { RedrawWindow(handle, None, None, RDW_INVALIDATE | RDW_UPDATENOW) };
+        return LRESULT(0);
     } else if message == WM_PAINT {
         paint_draw_window(handle);
+    } else if message == WM_MOUSEMOVE || message == WM_LBUTTONDOWN || message == WM_RBUTTONDOWN {
+        handle_mouse(handle, message, lparam);
+    } else if message == WM_CHAR {
+        let mut state_guard = lock_state();
+        let typed = char::from_u32(wparam.0 as u32);
+        if let (Some(entry), Some(c)) = (&mut state_guard.coordinate_entry, typed) {
+            if c.is_ascii_digit() || c == ' ' || c == ',' {
+                entry.push(c);
+                let title = window_title(&state_guard);
+                drop(state_guard);
+                set_window_title(handle, &title);
+            }
+        }
+        return LRESULT(0);
     } else if message == WM_KEYDOWN {
         let key: VIRTUAL_KEY = match wparam.0.try_into() {
             Ok(v) => VIRTUAL_KEY(v),
@@ -93,56 +1160,208 @@ unsafe extern "system" fn draw_window_proc(handle: HWND, message: u32, wparam: W
         };
 
         let mut redraw = true;
+        let mut load_underlay = false;
+        let mut import_reference = false;
+        let mut save_project_file = false;
+        let mut save_document: Option<(String, Option<String>)> = None;
+        let mut save_selection: Option<(String, usize)> = None;
+        let mut open_project_file = false;
+        let mut export_emf_file = false;
+        let mut export_png_file = false;
+        let mut new_document = false;
+        let mut print_page = false;
+        let mut error_message: Option<String> = None;
+        let mut title;
 
         {
-            let mut state_guard = STATE.lock().expect("failed to lock state");
-            if key == VK_LEFT {
-                state_guard.cursor.x -= HORIZONTAL_FACTOR;
+            let mut state_guard = lock_state();
+            let shift_pressed = unsafe { GetKeyState(VK_SHIFT.0.into()) } < 0;
+            let ctrl_pressed = unsafe { GetKeyState(VK_CONTROL.0.into()) } < 0;
+            let canvas = state_guard.canvas;
+            let step_multiplier = state_guard.cursor_step_multiplier(key);
+            let (horizontal_step, vertical_step) = if shift_pressed {
+                // fine movement
+                (1, 1)
+            } else if ctrl_pressed {
+                // coarse movement
+                (canvas.horizontal_factor * COARSE_STEP_CELLS, canvas.vertical_factor * COARSE_STEP_CELLS)
+            } else {
+                (canvas.horizontal_factor * step_multiplier, canvas.vertical_factor * step_multiplier)
+            };
+            let path_count_before = state_guard.paths.len();
+            let is_history_key = ctrl_pressed && (key == VK_Z || key == VK_Y);
+            let before = state_guard.undo_marker();
+            if state_guard.coordinate_entry.is_some() {
+                // the coordinate entry takes all keys until it is confirmed or cancelled;
+                // the digits themselves arrive as WM_CHAR
+                if key == VK_RETURN {
+                    state_guard.confirm_coordinate_entry(shift_pressed);
+                } else if key == VK_ESCAPE {
+                    state_guard.coordinate_entry = None;
+                    state_guard.log("coordinate entry cancelled");
+                } else if key == VK_BACK {
+                    if let Some(entry) = &mut state_guard.coordinate_entry {
+                        entry.pop();
+                    }
+                }
+            } else if ctrl_pressed && key == VK_Z {
+                state_guard.undo();
+            } else if ctrl_pressed && key == VK_Y {
+                state_guard.redo();
+            } else if let (true, true, Some(direction)) = (ctrl_pressed, shift_pressed, arrow_direction(key)) {
+                // pan the view
+                let step = state_guard.screen_zoom().unscale(PAN_STEP);
+                state_guard.viewport.x += direction.x * step;
+                state_guard.viewport.y += direction.y * step;
+            } else if let (true, Some(direction)) = (state_guard.adjusting_underlay, arrow_direction(key)) {
+                // move the reference image
+                if let Some(underlay) = state_guard.underlay.as_mut() {
+                    underlay.offset.x += direction.x * canvas.horizontal_factor * step_multiplier;
+                    underlay.offset.y += direction.y * canvas.vertical_factor * step_multiplier;
+                }
+            } else if state_guard.adjusting_underlay && (key == VK_PRIOR || key == VK_NEXT) {
+                // grow (Page Up) or shrink (Page Down) the reference image
+                if let Some(underlay) = state_guard.underlay.as_mut() {
+                    let delta = if key == VK_PRIOR { UNDERLAY_SCALE_STEP } else { -UNDERLAY_SCALE_STEP };
+                    underlay.rescale(delta);
+                }
+            } else if let (true, Some(direction)) = (state_guard.edit_mode, arrow_direction(key)) {
+                if shift_pressed {
+                    // Shift+Left/Up extends to the previous vertex, Shift+Right/Down to the next
+                    extend_selection(&mut state_guard, direction.x > 0 || direction.y > 0);
+                } else {
+                    // by one cell, accelerated if enabled (Ctrl: by five cells)
+                    let delta = Point {
+                        x: direction.x * horizontal_step,
+                        y: direction.y * vertical_step,
+                    };
+                    move_selection(&mut state_guard, delta);
+                }
+            } else if key == VK_LEFT {
+                state_guard.cursor.x -= horizontal_step;
                 if state_guard.cursor.x < 0 {
                     state_guard.cursor.x = 0;
                 }
             } else if key == VK_RIGHT {
-                state_guard.cursor.x += HORIZONTAL_FACTOR;
+                state_guard.cursor.x += horizontal_step;
             } else if key == VK_UP {
-                state_guard.cursor.y -= VERTICAL_FACTOR;
+                state_guard.cursor.y -= vertical_step;
                 if state_guard.cursor.y < 0 {
                     state_guard.cursor.y = 0;
                 }
             } else if key == VK_DOWN {
-                state_guard.cursor.y += VERTICAL_FACTOR;
+                state_guard.cursor.y += vertical_step;
+            } else if ctrl_pressed && key == VK_SPACE {
+                state_guard.drop_control_point();
+            } else if shift_pressed && key == VK_SPACE {
+                state_guard.start_subpath();
             } else if key == VK_SPACE {
-                let cursor = state_guard.cursor;
-                if !state_guard.is_drawing {
-                    // start a new path
-                    state_guard.paths.push(ClosedPath::default());
-                }
-                let last_path = state_guard.paths.last_mut().unwrap();
-
-                // drop a point
-                last_path.points.push(cursor);
-
-                // we are certainly drawing now
-                state_guard.is_drawing = true;
+                state_guard.drop_point();
+            } else if key == VK_I {
+                // type in where the cursor should go
+                state_guard.coordinate_entry = Some(String::new());
+                state_guard.log("enter X and Y, then press Return (Shift: also drop a point) or Escape");
             } else if key == VK_BACK {
                 // forget the last point
                 if let Some(last_path) = state_guard.paths.last_mut() {
-                    last_path.points.pop();
+                    last_path.pop_point();
+                }
+                if let (true, Some(mirror_path_index)) = (state_guard.is_drawing, state_guard.mirror_path_index) {
+                    if let Some(mirror_path) = state_guard.paths.get_mut(mirror_path_index) {
+                        mirror_path.pop_point();
+                    }
+                }
+            } else if key == VK_OEM_COMMA || key == VK_OEM_PERIOD {
+                // let vertices and edges attract the cursor from less (,) or more (.) far away
+                if key == VK_OEM_COMMA {
+                    state_guard.snap_distance.shrink();
+                } else {
+                    state_guard.snap_distance.grow();
                 }
+                let message = format!("snapping distance: {}", state_guard.snap_distance.0);
+                state_guard.log(message);
+            } else if key == VK_F5 {
+                state_guard.resume_path();
             } else if key == VK_RETURN {
-                // finish this path
-                state_guard.is_drawing = false;
+                // finish this path (Shift: leave it open)
+                if shift_pressed {
+                    state_guard.finish_open_path();
+                } else {
+                    state_guard.finish_path();
+                }
             } else if key == VK_ESCAPE {
                 // stop drawing and forget the last path
                 state_guard.paths.pop();
+                if let (true, Some(_)) = (state_guard.is_drawing, state_guard.mirror_path_index) {
+                    // the mirror image is right before it
+                    state_guard.paths.pop();
+                }
                 state_guard.is_drawing = false;
+                state_guard.mirror_path_index = None;
+            } else if ctrl_pressed && shift_pressed && key == VK_S {
+                // save only the selected paths as an SVG document
+                let path_indexes: BTreeSet<usize> = state_guard.selected_path_indexes().into_iter().collect();
+                if path_indexes.len() == 0 {
+                    state_guard.log("no path selected");
+                } else {
+                    // (asking for the file name once the state is unlocked)
+                    let svg = assemble_svg_subset(
+                        state_guard.grid_count, state_guard.canvas, &state_guard.paths, &path_indexes,
+                        &state_guard.svg_options,
+                    );
+                    save_selection = Some((svg, path_indexes.len()));
+                }
+                redraw = false;
+            } else if ctrl_pressed && key == VK_S {
+                // save the drawing as a project (once the state is unlocked)
+                save_project_file = true;
+                redraw = false;
+            } else if ctrl_pressed && key == VK_O {
+                // open a project (once the state is unlocked)
+                open_project_file = true;
+            } else if ctrl_pressed && key == VK_P {
+                // print the drawing (once the state is unlocked)
+                print_page = true;
+                redraw = false;
             } else if key == VK_P {
                 // print SVG document
-                let svg = assemble_svg(state_guard.grid_count, &state_guard.paths);
+                let svg = assemble_svg(
+                    state_guard.grid_count, state_guard.canvas, &state_guard.paths, &state_guard.svg_options,
+                );
                 println!("{}", svg);
+                state_guard.log("printed SVG to standard output");
             } else if key == VK_S {
-                // save SVG document
-                let svg = assemble_svg(state_guard.grid_count, &state_guard.paths);
-                save_svg(handle, &svg);
+                // save SVG document (Shift: with a report on the paths next to it), asking for the
+                // file name once the state is unlocked
+                let svg = assemble_svg(
+                    state_guard.grid_count, state_guard.canvas, &state_guard.paths, &state_guard.svg_options,
+                );
+                let report = shift_pressed.then(|| assemble_report(&state_guard.paths));
+                save_document = Some((svg, report));
+                redraw = false;
+            } else if ctrl_pressed && (key == VK_H || key == VK_V) {
+                // widen/narrow (H) or heighten/flatten (V) the grid cells
+                let delta = if shift_pressed { -GRID_FACTOR_STEP } else { GRID_FACTOR_STEP };
+                let factor = if key == VK_H {
+                    &mut state_guard.canvas.horizontal_factor
+                } else {
+                    &mut state_guard.canvas.vertical_factor
+                };
+                *factor = (*factor + delta).clamp(MIN_GRID_FACTOR, MAX_GRID_FACTOR);
+                let message = format!(
+                    "grid cell size: {}\u{D7}{}",
+                    state_guard.canvas.horizontal_factor, state_guard.canvas.vertical_factor,
+                );
+                state_guard.log(message);
+            } else if key == VK_OEM_PLUS || key == VK_ADD || key == VK_OEM_MINUS || key == VK_SUBTRACT {
+                // zoom in/out
+                if key == VK_OEM_PLUS || key == VK_ADD {
+                    state_guard.zoom.zoom_in();
+                } else {
+                    state_guard.zoom.zoom_out();
+                }
+                let message = format!("zoom: {}%", state_guard.zoom.percent());
+                state_guard.log(message);
             } else if key == VK_H {
                 // increase/decrease horizontal grid
                 let shift_state = unsafe { GetKeyState(VK_SHIFT.0.into()) };
@@ -152,6 +1371,11 @@ unsafe extern "system" fn draw_window_proc(handle: HWND, message: u32, wparam: W
                     if state_guard.grid_count.x < 0 {
                         state_guard.grid_count.x = 0;
                     }
+                    state_guard.warn_about_points_outside_grid();
+                } else if state_guard.grid_count.x >= state_guard.grid_limit.0.x {
+                    // painting many more lines would make the window unresponsive
+                    let message = format!("the grid cannot be wider than {} cells", state_guard.grid_limit.0.x);
+                    state_guard.log(message);
                 } else {
                     // increase
                     state_guard.grid_count.x += 1;
@@ -166,186 +1390,1765 @@ unsafe extern "system" fn draw_window_proc(handle: HWND, message: u32, wparam: W
                     if state_guard.grid_count.y < 0 {
                         state_guard.grid_count.y = 0;
                     }
+                    state_guard.warn_about_points_outside_grid();
+                } else if state_guard.grid_count.y >= state_guard.grid_limit.0.y {
+                    // painting many more lines would make the window unresponsive
+                    let message = format!("the grid cannot be higher than {} cells", state_guard.grid_limit.0.y);
+                    state_guard.log(message);
                 } else {
                     // increase
                     state_guard.grid_count.y += 1;
                 }
                 redraw = true;
+            } else if ctrl_pressed && key == VK_B {
+                // export a PNG image (once the state is unlocked)
+                if state_guard.grid_count.x > 0 && state_guard.grid_count.y > 0 {
+                    export_png_file = true;
+                    redraw = false;
+                } else {
+                    state_guard.log("PNG export requires a grid");
+                }
+            } else if shift_pressed && key == VK_B {
+                // paint the exported SVG on white instead of leaving it transparent
+                state_guard.svg_options.background = match state_guard.svg_options.background {
+                    Some(_) => None,
+                    None => Some(WHITE.0),
+                };
+                let message = format!("SVG background {}", on_off(state_guard.svg_options.background.is_some()));
+                state_guard.log(message);
+            } else if key == VK_B {
+                // toggle the canvas backdrop
+                state_guard.fill_canvas = !state_guard.fill_canvas;
+                let message = format!("canvas backdrop {}", on_off(state_guard.fill_canvas));
+                state_guard.log(message);
+            } else if key == VK_K {
+                // toggle crisp edges in the exported SVG
+                state_guard.svg_options.crisp_edges = !state_guard.svg_options.crisp_edges;
+                let message = format!("crisp SVG edges {}", on_off(state_guard.svg_options.crisp_edges));
+                state_guard.log(message);
+            } else if key == VK_HOME {
+                // zoom and pan so that the grid and all paths fill the window
+                let mut client_rect = RECT::default();
+                if unsafe { GetClientRect(handle, &mut client_rect) }.is_ok() {
+                    state_guard.client_size = Point {
+                        x: client_rect.right - client_rect.left,
+                        y: client_rect.bottom - client_rect.top,
+                    };
+                }
+                let mut min = Point::default();
+                let mut max = state_guard.grid_extent();
+                for (path_min, path_max) in state_guard.paths.iter().filter_map(|path| path.bounding_box()) {
+                    min.x = min.x.min(path_min.x);
+                    min.y = min.y.min(path_min.y);
+                    max.x = max.x.max(path_max.x);
+                    max.y = max.y.max(path_max.y);
+                }
+                if min == max {
+                    state_guard.log("nothing to fit to the window");
+                } else {
+                    state_guard.frame_region(min, max, FIT_MARGIN, true);
+                    let message = format!("zoom: {}%", state_guard.zoom.percent());
+                    state_guard.log(message);
+                }
+            } else if key == VK_F8 {
+                // change what happens when the window is resized, and apply it right away
+                state_guard.resize_mode = state_guard.resize_mode.next();
+                state_guard.apply_resize_mode();
+                let message = format!("on resize: {}", state_guard.resize_mode.name());
+                state_guard.log(message);
+            } else if key == VK_F7 {
+                // export coordinates in grid cells instead of drawing coordinates
+                state_guard.svg_options.cell_units = !state_guard.svg_options.cell_units;
+                let message = format!("SVG in grid cell units {}", on_off(state_guard.svg_options.cell_units));
+                state_guard.log(message);
+            } else if key == VK_F9 {
+                // give each exported shape an id for scripts and stylesheets to refer to
+                state_guard.svg_options.path_ids = !state_guard.svg_options.path_ids;
+                let message = format!("SVG shape ids {}", on_off(state_guard.svg_options.path_ids));
+                state_guard.log(message);
+            } else if shift_pressed && key == VK_O {
+                // cycle how shapes are painted in the exported SVG
+                state_guard.svg_options.style = state_guard.svg_options.style.next();
+                let message = format!("SVG style: {}", state_guard.svg_options.style.name());
+                state_guard.log(message);
+            } else if key == VK_F3 {
+                // switch the colors of the window (but not of exports) between light and dark
+                state_guard.dark_mode = !state_guard.dark_mode;
+                let message = format!("dark mode {}", on_off(state_guard.dark_mode));
+                state_guard.log(message);
+            } else if key == VK_O {
+                // switch between filled and outlined paths on screen, e.g. to find self-intersections
+                state_guard.outline_preview = !state_guard.outline_preview;
+                let message = format!("outline preview {}", on_off(state_guard.outline_preview));
+                state_guard.log(message);
+            } else if ctrl_pressed && shift_pressed && key == VK_C {
+                // copy only the outlines, merged into one path definition, for use in other documents
+                let path_data = build_path_data(state_guard.canvas, &state_guard.paths, &state_guard.svg_options);
+                match copy_text(handle, &path_data) {
+                    Ok(()) => state_guard.log("copied SVG path data to the clipboard"),
+                    Err(e) => state_guard.log(format!("error copying SVG path data to the clipboard: {}", e)),
+                }
+            } else if ctrl_pressed && key == VK_C {
+                // copy SVG document to the clipboard
+                let svg = assemble_svg(
+                    state_guard.grid_count, state_guard.canvas, &state_guard.paths, &state_guard.svg_options,
+                );
+                match copy_text(handle, &svg) {
+                    Ok(()) => state_guard.log("copied SVG to the clipboard"),
+                    Err(e) => state_guard.log(format!("error copying SVG to the clipboard: {}", e)),
+                }
+            } else if key == VK_C {
+                // toggle cell paint mode; this finishes any path being drawn
+                state_guard.cell_paint = !state_guard.cell_paint;
+                state_guard.is_drawing = false;
+                let message = format!("cell paint mode {}", on_off(state_guard.cell_paint));
+                state_guard.log(message);
+            } else if shift_pressed && key == VK_F {
+                // switch how overlapping subpaths are filled, on screen and in exports
+                state_guard.svg_options.fill_rule = state_guard.svg_options.fill_rule.next();
+                let message = format!("fill rule: {}", state_guard.svg_options.fill_rule.name());
+                state_guard.log(message);
+            } else if key == VK_F {
+                // flood-fill (or flood-clear) the cells connected to the one under the cursor
+                let grid_shift = state_guard.grid_shift();
+                let start = Point {
+                    x: (state_guard.cursor.x - grid_shift.x).div_euclid(canvas.horizontal_factor),
+                    y: (state_guard.cursor.y - grid_shift.y).div_euclid(canvas.vertical_factor),
+                };
+                let grid_count = state_guard.grid_count;
+                state_guard.is_drawing = false;
+                let color = state_guard.cell_color;
+                flood_fill(&mut state_guard.paths, grid_count, canvas, grid_shift, start, color);
+            } else if ctrl_pressed && key == VK_N {
+                // start a new drawing (once the state is unlocked)
+                new_document = true;
+                redraw = false;
+            } else if key == VK_N {
+                // cycle through the snapping modes
+                state_guard.snap_mode = state_guard.snap_mode.next();
+                let message = format!("snapping: {}", state_guard.snap_mode.name());
+                state_guard.log(message);
+            } else if key == VK_F4 {
+                // switch between plain and antialiased rendering of paths on screen
+                state_guard.antialias = !state_guard.antialias;
+                let message = format!("antialiasing {}", on_off(state_guard.antialias));
+                state_guard.log(message);
+            } else if key == VK_F1 {
+                // show/hide the help
+                state_guard.show_help = !state_guard.show_help;
+            } else if key == VK_L {
+                // show/hide the log
+                state_guard.show_log = !state_guard.show_log;
+            } else if ctrl_pressed && key == VK_E {
+                // export an Enhanced Metafile (once the state is unlocked)
+                export_emf_file = true;
+                redraw = false;
+            } else if key == VK_E {
+                // enter/leave vertex editing mode
+                state_guard.edit_mode = !state_guard.edit_mode;
+                state_guard.is_drawing = false;
+                state_guard.selected_vertices.clear();
+                state_guard.vertex_cursor = None;
+                if state_guard.edit_mode {
+                    // start at the most recently placed vertex
+                    cycle_vertex(&mut state_guard, false);
+                }
+            } else if ctrl_pressed && key == VK_A {
+                // select every path, e.g. to move the whole drawing
+                let selected: BTreeSet<usize> = state_guard.paths.iter()
+                    .enumerate()
+                    .filter(|(_path_index, path)| path.points.len() > 0)
+                    .map(|(path_index, _path)| path_index)
+                    .collect();
+                let message = format!("selected {} paths", selected.len());
+                state_guard.selected_paths = selected;
+                state_guard.edit_mode = false;
+                state_guard.selected_vertices.clear();
+                state_guard.vertex_cursor = None;
+                state_guard.log(message);
+            } else if key == VK_A {
+                // toggle cursor acceleration
+                state_guard.accelerate_cursor = !state_guard.accelerate_cursor;
+                let message = format!("cursor acceleration {}", on_off(state_guard.accelerate_cursor));
+                state_guard.log(message);
+                redraw = false;
+            } else if key == VK_D {
+                duplicate_selected_paths(&mut state_guard);
+            } else if key == VK_M {
+                if shift_pressed {
+                    // switch the mirror axis
+                    state_guard.mirror_axis = state_guard.mirror_axis.other();
+                    let message = format!("mirror axis: {}", state_guard.mirror_axis.name());
+                    state_guard.log(message);
+                } else if state_guard.grid_count.x == 0 || state_guard.grid_count.y == 0 {
+                    // the grid defines the mirror line
+                    state_guard.log("cannot mirror without a grid");
+                } else {
+                    // append mirrored copies of the selected paths
+                    for path_index in state_guard.selected_path_indexes() {
+                        let mut mirrored = state_guard.paths[path_index].clone();
+                        mirrored.mirror(state_guard.mirror_axis, state_guard.grid_count, canvas);
+                        state_guard.is_drawing = false;
+                        state_guard.paths.push(mirrored);
+                    }
+                }
+            } else if key == VK_X {
+                if state_guard.grid_count.x == 0 || state_guard.grid_count.y == 0 {
+                    // the grid defines the mirror line
+                    state_guard.log("cannot mirror without a grid");
+                } else {
+                    // mirror the selected paths in place
+                    let (axis, grid_count) = (state_guard.mirror_axis, state_guard.grid_count);
+                    for path_index in state_guard.selected_path_indexes() {
+                        state_guard.paths[path_index].mirror(axis, grid_count, canvas);
+                    }
+                }
+            } else if let (true, Some(direction)) = (state_guard.edit_mode, numpad_direction(key)) {
+                // nudge the selected vertices (Shift: by one unit, Ctrl: by five cells)
+                let delta = Point {
+                    x: direction.x * horizontal_step,
+                    y: direction.y * vertical_step,
+                };
+                move_selection(&mut state_guard, delta);
+            } else if let Some(direction) = numpad_direction(key) {
+                // move the selected paths
+                let path_indexes = state_guard.selected_path_indexes();
+                translate_paths(
+                    &mut state_guard.paths, &path_indexes,
+                    direction.x * horizontal_step, direction.y * vertical_step,
+                );
+            } else if ctrl_pressed && key == VK_R {
+                if shift_pressed {
+                    // drop the reference drawing
+                    if state_guard.reference_paths.len() > 0 {
+                        state_guard.reference_paths.clear();
+                        state_guard.log("removed reference drawing");
+                    }
+                } else {
+                    // import a reference drawing (once the state is unlocked)
+                    import_reference = true;
+                }
+            } else if key == VK_R {
+                // rotate the selected paths clockwise (Shift: counterclockwise)
+                let path_indexes = state_guard.selected_path_indexes();
+                rotate_paths_90(&mut state_guard.paths, &path_indexes, !shift_pressed);
+            } else if key == VK_Y {
+                // toggle live symmetry for newly drawn paths
+                if !state_guard.symmetry && (state_guard.grid_count.x == 0 || state_guard.grid_count.y == 0) {
+                    state_guard.log("cannot draw symmetrically without a grid");
+                } else {
+                    state_guard.symmetry = !state_guard.symmetry;
+                    let message = format!("symmetry {}", on_off(state_guard.symmetry));
+                    state_guard.log(message);
+                }
+            } else if key == VK_U {
+                if shift_pressed {
+                    // show/hide the reference image
+                    if let Some(underlay) = state_guard.underlay.as_mut() {
+                        underlay.visible = !underlay.visible;
+                    }
+                } else {
+                    // load a reference image (once the state is unlocked)
+                    load_underlay = true;
+                }
+            } else if key == VK_T {
+                let state = &mut *state_guard;
+                if let Some(underlay) = state.underlay.as_mut() {
+                    if shift_pressed {
+                        // lock/unlock the placement of the reference image
+                        underlay.locked = !underlay.locked;
+                        state.adjusting_underlay = false;
+                        let message = format!("reference image {}", if underlay.locked { "locked" } else { "unlocked" });
+                        state.log(message);
+                    } else if underlay.locked {
+                        state.log("the reference image is locked");
+                    } else {
+                        // start/stop moving and scaling the reference image
+                        state.adjusting_underlay = !state.adjusting_underlay;
+                    }
+                }
+            } else if shift_pressed && (key == VK_OEM_4 || key == VK_OEM_6) {
+                // shorten ([) or lengthen (]) the crosshair
+                if key == VK_OEM_4 {
+                    state_guard.crosshair_length.shrink();
+                } else {
+                    state_guard.crosshair_length.grow();
+                }
+                let message = format!("crosshair length: {}", state_guard.crosshair_length.0);
+                state_guard.log(message);
+            } else if key == VK_OEM_4 || key == VK_OEM_6 {
+                // make the reference image more transparent ([) or more opaque (])
+                if let Some(underlay) = state_guard.underlay.as_mut() {
+                    underlay.opacity = if key == VK_OEM_4 {
+                        underlay.opacity.saturating_sub(UNDERLAY_OPACITY_STEP)
+                    } else {
+                        underlay.opacity.saturating_add(UNDERLAY_OPACITY_STEP)
+                    };
+                }
+            } else if shift_pressed && key == VK_J {
+                // reverse the winding direction, e.g. to turn a hole that fills solid into a cut-out
+                state_guard.is_drawing = false;
+                if let (true, Some((path_index, point_index))) = (state_guard.edit_mode, state_guard.vertex_cursor) {
+                    // only the subpath with the vertex cursor
+                    let path = &mut state_guard.paths[path_index];
+                    let range = path.subpath_ranges()
+                        .into_iter()
+                        .find(|range| range.contains(&point_index));
+                    if let Some(range) = range {
+                        path.reverse_subpath(range);
+                    }
+                } else {
+                    for path_index in state_guard.selected_path_indexes() {
+                        state_guard.paths[path_index].reverse();
+                    }
+                }
+                state_guard.log("reversed path direction");
+
+                // the vertex indices have changed
+                state_guard.selected_vertices.clear();
+                state_guard.vertex_cursor = None;
+            } else if key == VK_J {
+                // normalize all closed paths to start at their top-left-most vertex;
+                // the endpoints of open paths are part of their shape
+                state_guard.is_drawing = false;
+                for path in state_guard.paths.iter_mut().filter(|path| path.closed) {
+                    path.normalize_start();
+                }
+
+                // the vertex indices have changed
+                state_guard.selected_vertices.clear();
+                state_guard.vertex_cursor = None;
+            } else if key == VK_Q {
+                if let Some(path_index) = state_guard.selected_path_index() {
+                    let path = &mut state_guard.paths[path_index];
+                    if shift_pressed {
+                        // cycle the tension of the smoothed path
+                        let tension_index = SPLINE_TENSIONS_PERCENT.iter()
+                            .position(|t| *t == path.tension_percent)
+                            .map(|i| (i + 1) % SPLINE_TENSIONS_PERCENT.len())
+                            .unwrap_or(0);
+                        path.tension_percent = SPLINE_TENSIONS_PERCENT[tension_index];
+                    } else {
+                        // toggle between straight edges and a smooth spline
+                        path.smooth = !path.smooth;
+                        path.cell = false;
+                    }
+                    let message = format!(
+                        "path {}: smoothing {}, tension {}%",
+                        path_index, on_off(path.smooth), path.tension_percent,
+                    );
+                    state_guard.log(message);
+                }
+            } else if shift_pressed && key == VK_F2 {
+                // cycle the crosshair colors
+                state_guard.crosshair_colors = state_guard.crosshair_colors.next();
+                let message = format!("crosshair colors: {}", state_guard.crosshair_colors.name());
+                state_guard.log(message);
+            } else if key == VK_F2 && state_guard.cell_paint {
+                // cycle the color that cells are painted with
+                let color_index = PATH_PALETTE.iter()
+                    .position(|c| c.0 == state_guard.cell_color)
+                    .map(|i| (i + 1) % PATH_PALETTE.len())
+                    .unwrap_or(0);
+                state_guard.cell_color = PATH_PALETTE[color_index].0;
+                let message = format!("cell color {}", css_color(state_guard.cell_color));
+                state_guard.log(message);
+            } else if key == VK_F2 {
+                // cycle the color of the selected path
+                if let Some(path_index) = state_guard.selected_path_index() {
+                    let path = &mut state_guard.paths[path_index];
+                    let color_index = PATH_PALETTE.iter()
+                        .position(|c| c.0 == path.color)
+                        .map(|i| (i + 1) % PATH_PALETTE.len())
+                        .unwrap_or(0);
+                    path.color = PATH_PALETTE[color_index].0;
+                    let message = format!("path {}: color {}", path_index, css_color(path.color));
+                    state_guard.log(message);
+                }
+            } else if key == VK_F6 {
+                let cursor = state_guard.cursor;
+                if shift_pressed {
+                    // move the export frame to the cursor
+                    if let Some(frame) = state_guard.svg_options.frame.as_mut() {
+                        frame.origin = cursor;
+                    }
+                } else if state_guard.svg_options.frame.is_some() {
+                    // go back to exporting the grid
+                    state_guard.svg_options.frame = None;
+                    state_guard.log("exporting the grid");
+                } else {
+                    // export a frame as wide as the grid, starting at the cursor
+                    let width = if state_guard.grid_count.x > 0 {
+                        state_guard.grid_count.x * canvas.horizontal_factor
+                    } else {
+                        EXPORT_FRAME_DEFAULT_CELLS * canvas.horizontal_factor
+                    };
+                    let frame_settings = state_guard.frame_settings;
+                    state_guard.svg_options.frame = Some(ExportFrame {
+                        origin: cursor,
+                        size: Point {
+                            x: width,
+                            y: frame_settings.height(width),
+                        },
+                        output_size: frame_settings.output_size(),
+                    });
+                    state_guard.log("exporting the frame");
+                }
+            } else if ctrl_pressed && shift_pressed && key == VK_G {
+                // let the grid begin at the cursor, e.g. to line it up with a reference image
+                let cursor = state_guard.cursor;
+                state_guard.set_grid_origin(cursor);
+                let grid_shift = state_guard.grid_shift();
+                let message = format!("grid lines shifted by ({}, {})", grid_shift.x, grid_shift.y);
+                state_guard.log(message);
+            } else if ctrl_pressed && key == VK_G {
+                // switch between dashed and solid grid lines
+                state_guard.solid_grid = !state_guard.solid_grid;
+                let message = format!("solid grid lines {}", on_off(state_guard.solid_grid));
+                state_guard.log(message);
+            } else if shift_pressed && key == VK_G {
+                // keep the cursor on the grid or let it roam freely
+                state_guard.clamp_to_grid = !state_guard.clamp_to_grid;
+                let message = format!("keep cursor on grid {}", on_off(state_guard.clamp_to_grid));
+                state_guard.log(message);
+            } else if key == VK_W {
+                // only draw horizontal, vertical and diagonal edges
+                state_guard.constrain_angles = !state_guard.constrain_angles;
+                let message = format!("45\u{B0} edges {}", on_off(state_guard.constrain_angles));
+                state_guard.log(message);
+            } else if key == VK_G {
+                // quickly toggle snapping to the grid
+                state_guard.snap_mode = if state_guard.snap_mode == SnapMode::Grid {
+                    SnapMode::Off
+                } else {
+                    SnapMode::Grid
+                };
+                let message = format!("snapping: {}", state_guard.snap_mode.name());
+                state_guard.log(message);
+            } else if key == VK_TAB && state_guard.edit_mode {
+                // select the next/previous vertex
+                cycle_vertex(&mut state_guard, !shift_pressed);
+            } else if key == VK_TAB {
+                // select the next/previous path
+                cycle_path(&mut state_guard, !shift_pressed);
+            } else if key == VK_INSERT && state_guard.edit_mode {
+                // split the edge after the selected vertex
+                insert_midpoint(&mut state_guard);
+            } else if key == VK_DELETE && state_guard.edit_mode {
+                delete_selected_vertices(&mut state_guard);
+            } else if key == VK_DELETE && !state_guard.edit_mode {
+                delete_selected_paths(&mut state_guard);
             } else {
                 // unknown key -- don't redraw
                 redraw = false;
             }
+
+            state_guard.clamp_cursor();
+            enforce_path_limits(&mut state_guard, path_count_before);
+            if !is_history_key {
+                state_guard.record_undo(before);
+            }
+            prune_selection(&mut state_guard);
+            title = window_title(&state_guard);
+        }
+
+        if load_underlay {
+            let filter = w!("Images (*.png;*.jpg;*.jpeg)\0*.png;*.jpg;*.jpeg\0All Files (*.*)\0*.*\0\0");
+            if let Some(path) = open_file(handle, filter) {
+                let loaded = Underlay::load(&path);
+                let mut state_guard = lock_state();
+                match loaded {
+                    Ok(underlay) => {
+                        state_guard.underlay = Some(underlay);
+                        forget_scaled_bitmap();
+                        state_guard.log(format!("loaded reference image {}", path.display()));
+                    },
+                    Err(e) => {
+                        state_guard.log(format!("error loading reference image {}: {}", path.display(), e));
+                    },
+                }
+            }
+        }
+
+        if import_reference {
+            let filter = w!("SVG Files (*.svg)\0*.svg\0All Files (*.*)\0*.*\0\0");
+            if let Some(path) = open_file(handle, filter) {
+                let loaded = load_reference(&path);
+                let mut state_guard = lock_state();
+                match loaded {
+                    Ok(reference_paths) => {
+                        let message = format!(
+                            "imported {} reference paths from {}", reference_paths.len(), path.display(),
+                        );
+                        state_guard.reference_paths = reference_paths;
+                        state_guard.log(message);
+                    },
+                    Err(e) => {
+                        state_guard.log(format!("error importing reference drawing {}: {}", path.display(), e));
+                    },
+                }
+            }
+        }
+
+        if save_project_file {
+            save_drawing(handle);
+            title = window_title(&lock_state());
+        }
+
+        if let Some((svg, report)) = save_document {
+            let saved = save_svg(handle, &svg);
+            let mut state_guard = lock_state();
+            match saved {
+                None => {},
+                Some((path, Ok(()))) => {
+                    state_guard.log(format!("saved SVG to {}", path.display()));
+                    if let Some(report) = report {
+                        let report_path = path.with_extension("report.json");
+                        match std::fs::write(&report_path, report) {
+                            Ok(()) => state_guard.log(format!("saved report to {}", report_path.display())),
+                            Err(e) => {
+                                let message = format!("error writing report to {}: {}", report_path.display(), e);
+                                state_guard.log(message.clone());
+                                error_message = Some(message);
+                            },
+                        }
+                    }
+                    state_guard.current_file = Some(path);
+                    state_guard.dirty = false;
+                },
+                Some((path, Err(e))) => {
+                    let message = format!("error writing SVG to {}: {}", path.display(), e);
+                    state_guard.log(message.clone());
+                    error_message = Some(message);
+                },
+            }
+            title = window_title(&state_guard);
+        }
+
+        if let Some((svg, path_count)) = save_selection {
+            let saved = save_svg(handle, &svg);
+            let mut state_guard = lock_state();
+            match saved {
+                None => {},
+                Some((path, Ok(()))) => {
+                    state_guard.log(format!("saved {} selected paths to {}", path_count, path.display()));
+                },
+                Some((path, Err(e))) => {
+                    let message = format!("error writing SVG to {}: {}", path.display(), e);
+                    state_guard.log(message.clone());
+                    error_message = Some(message);
+                },
+            }
+        }
+
+        if export_emf_file {
+            let filter = w!("Enhanced Metafiles (*.emf)\0*.emf\0All Files (*.*)\0*.*\0\0");
+            if let Some(path) = save_file_name(handle, filter, w!("emf")) {
+                let mut state_guard = lock_state();
+                match export_emf(&path, &state_guard) {
+                    Ok(()) => state_guard.log(format!("exported EMF to {}", path.display())),
+                    Err(e) => {
+                        let message = format!("error exporting EMF to {}: {}", path.display(), e);
+                        state_guard.log(message.clone());
+                        error_message = Some(message);
+                    },
+                }
+            }
+        }
+
+        if export_png_file {
+            let filter = w!("PNG Images (*.png)\0*.png\0All Files (*.*)\0*.*\0\0");
+            if let Some(path) = save_file_name(handle, filter, w!("png")) {
+                let mut state_guard = lock_state();
+                match export_png(&path, &state_guard) {
+                    Ok(()) => state_guard.log(format!("exported PNG to {}", path.display())),
+                    Err(e) => {
+                        let message = format!("error exporting PNG to {}: {}", path.display(), e);
+                        state_guard.log(message.clone());
+                        error_message = Some(message);
+                    },
+                }
+            }
+        }
+
+        if print_page {
+            match print_drawing(handle) {
+                Ok(false) => {},
+                Ok(true) => lock_state().log("printed the drawing"),
+                Err(e) => {
+                    let message = format!("error printing: {}", e);
+                    lock_state().log(message.clone());
+                    error_message = Some(message);
+                },
+            }
+        }
+
+        if new_document && may_discard_changes(handle, w!("The drawing has unsaved changes. Save them as a project first?")) {
+            // start over, keeping only the layout of the grid and the log
+            let mut state_guard = lock_state();
+            let state = &mut *state_guard;
+            *state = ApplicationState {
+                grid_count: state.grid_count,
+                canvas: state.canvas,
+                dpi: state.dpi,
+                client_size: state.client_size,
+                log: std::mem::take(&mut state.log),
+                ..ApplicationState::default()
+            };
+            state.log("started a new drawing");
+            title = window_title(&state_guard);
+            redraw = true;
+        }
+
+        if open_project_file {
+            if let Some(path) = open_file(handle, OPEN_FILTER) {
+                open_drawing(path);
+                title = window_title(&lock_state());
+            }
+        }
+
+        set_window_title(handle, &title);
+
+        if redraw {
+            unsafe { RedrawWindow(handle, None, None, RDW_INVALIDATE | RDW_UPDATENOW) };
+        }
+
+        if let Some(message) = error_message {
+            show_error(handle, &message);
+        }
+    }
+
+    default_window_proc(handle, message, wparam, lparam)
+}
+
+
+fn arrow_direction(key: VIRTUAL_KEY) -> Option<Point> {
+    if key == VK_LEFT {
+        Some(Point { x: -1, y: 0 })
+    } else if key == VK_RIGHT {
+        Some(Point { x: 1, y: 0 })
+    } else if key == VK_UP {
+        Some(Point { x: 0, y: -1 })
+    } else if key == VK_DOWN {
+        Some(Point { x: 0, y: 1 })
+    } else {
+        None
+    }
+}
+
+
+fn numpad_direction(key: VIRTUAL_KEY) -> Option<Point> {
+    if key == VK_NUMPAD4 {
+        Some(Point { x: -1, y: 0 })
+    } else if key == VK_NUMPAD6 {
+        Some(Point { x: 1, y: 0 })
+    } else if key == VK_NUMPAD8 {
+        Some(Point { x: 0, y: -1 })
+    } else if key == VK_NUMPAD2 {
+        Some(Point { x: 0, y: 1 })
+    } else {
+        None
+    }
+}
+
+
+fn handle_mouse(handle: HWND, message: u32, lparam: LPARAM) {
+    let title;
+
+    {
+        let mut state_guard = lock_state();
+        let position = screen_to_cursor(
+            mouse_position(lparam), state_guard.canvas.panned(state_guard.viewport), state_guard.screen_zoom(),
+            state_guard.grid_shift(),
+        );
+        if message == WM_MOUSEMOVE && state_guard.cursor == position {
+            // nothing to update
+            return;
+        }
+        let path_count_before = state_guard.paths.len();
+        let before = (message != WM_MOUSEMOVE).then(|| state_guard.undo_marker());
+
+        state_guard.cursor = position;
+        state_guard.clamp_cursor();
+        if message == WM_LBUTTONDOWN {
+            state_guard.drop_point();
+        } else if message == WM_RBUTTONDOWN {
+            state_guard.finish_path();
+        }
+
+        enforce_path_limits(&mut state_guard, path_count_before);
+        if let Some(before) = before {
+            state_guard.record_undo(before);
+        }
+        prune_selection(&mut state_guard);
+        title = window_title(&state_guard);
+    }
+
+    set_window_title(handle, &title);
+    unsafe { RedrawWindow(handle, None, None, RDW_INVALIDATE | RDW_UPDATENOW) };
+}
+
+
+fn mouse_position(lparam: LPARAM) -> Point {
+    // low word is x, high word is y; both are signed
+    let x = (lparam.0 & 0xFFFF) as u16 as i16;
+    let y = ((lparam.0 >> 16) & 0xFFFF) as u16 as i16;
+    Point {
+        x: x.into(),
+        y: y.into(),
+    }
+}
+
+
+fn screen_to_cursor(screen: Point, canvas: CanvasConfig, zoom: Zoom, grid_shift: Point) -> Point {
+    let x = zoom.unscale(screen.x) - canvas.left_offset - grid_shift.x;
+    let y = zoom.unscale(screen.y) - canvas.top_offset - grid_shift.y;
+    Point {
+        x: (round_to_multiple(x, canvas.horizontal_factor) + grid_shift.x).max(0),
+        y: (round_to_multiple(y, canvas.vertical_factor) + grid_shift.y).max(0),
+    }
+}
+
+
+fn paint_draw_window(handle: HWND) {
+    let mut paint_struct = PAINTSTRUCT::default();
+    let hdc = unsafe { BeginPaint(handle, &mut paint_struct) };
+    if hdc.is_invalid() {
+        return;
+    }
+
+    // paint into an off-screen bitmap, then copy it into the window in one go to avoid flicker
+    let mut client_rect = RECT::default();
+    let _ = unsafe { GetClientRect(handle, &mut client_rect) };
+    let size = Point {
+        x: (client_rect.right - client_rect.left).max(1),
+        y: (client_rect.bottom - client_rect.top).max(1),
+    };
+    let mut back_buffer_guard = BACK_BUFFER.lock().unwrap_or_else(PoisonError::into_inner);
+    let reusable = back_buffer_guard.as_ref()
+        .is_some_and(|(buffer_size, bitmap)| *buffer_size == size && !bitmap.handle().is_invalid());
+    if !reusable {
+        *back_buffer_guard = Some((size, make_compatible_bitmap(hdc, size.x, size.y)));
+    }
+    let (_size, back_buffer) = back_buffer_guard.as_ref().unwrap();
+
+    // the guards restore the DC and delete it in the reverse order of their creation
+    let memory_dc_owner = make_memory_dc(hdc);
+    let memory_dc = memory_dc_owner.handle();
+    let _bitmap_guard = select_object_scoped(memory_dc, back_buffer.handle(), "back buffer");
+
+    let failure;
+    {
+        // the DC must have its original pen and brush when it is deleted
+        let _pen_guard = preserve_selection(memory_dc, OBJ_PEN);
+        let _brush_guard = preserve_selection(memory_dc, OBJ_BRUSH);
+
+        let mut state_guard = lock_state();
+
+        // paint background
+        let background_brush = if state_guard.dark_mode {
+            DARK_BACKGROUND_BRUSH.handle()
+        } else {
+            let system_brush: isize = (COLOR_WINDOW.0 + 1).try_into().unwrap();
+            HBRUSH(system_brush)
+        };
+        unsafe { FillRect(memory_dc, &paint_struct.rcPaint, background_brush) };
+
+        render_scene(memory_dc, &state_guard);
+        failure = take_failure();
+        if let Some(message) = &failure {
+            state_guard.log(format!("error painting the window: {}", message));
+        }
+    }
+
+    if failure.is_none() {
+        // otherwise, keep what was painted before rather than showing a half-painted scene
+        let paint_rect = paint_struct.rcPaint;
+        let _ = unsafe {
+            BitBlt(
+                hdc,
+                paint_rect.left, paint_rect.top,
+                paint_rect.right - paint_rect.left, paint_rect.bottom - paint_rect.top,
+                memory_dc,
+                paint_rect.left, paint_rect.top,
+                SRCCOPY,
+            )
+        };
+    }
+    unsafe { EndPaint(handle, &paint_struct) };
+}
+
+
+fn render_scene(hdc: HDC, state: &ApplicationState) {
+    let canvas = state.canvas.panned(state.viewport);
+    let zoom = state.screen_zoom();
+
+    // paint the canvas
+    if state.fill_canvas && state.grid_count.x > 0 && state.grid_count.y > 0 {
+        let canvas_rect = RECT {
+            left: zoom.scale(canvas.left_offset),
+            top: zoom.scale(canvas.top_offset),
+            right: zoom.scale(canvas.left_offset + state.grid_count.x * canvas.horizontal_factor),
+            bottom: zoom.scale(canvas.top_offset + state.grid_count.y * canvas.vertical_factor),
+        };
+        let canvas_brush = if state.dark_mode { DARK_CANVAS_BRUSH.handle() } else { CANVAS_BRUSH.handle() };
+        unsafe { FillRect(hdc, &canvas_rect, canvas_brush) };
+    }
+
+    // paint the reference image
+    if let Some(underlay) = &state.underlay {
+        // fit it to the grid; without a grid, start from its natural size
+        let (base_width, base_height): (i32, i32) = if state.grid_count.x > 0 && state.grid_count.y > 0 {
+            (state.grid_count.x * canvas.horizontal_factor, state.grid_count.y * canvas.vertical_factor)
+        } else {
+            (underlay.width.try_into().unwrap(), underlay.height.try_into().unwrap())
+        };
+        let width = base_width * underlay.scale_percent / 100;
+        let height = base_height * underlay.scale_percent / 100;
+        let underlay_rect = RECT {
+            left: zoom.scale(canvas.left_offset + underlay.offset.x),
+            top: zoom.scale(canvas.top_offset + underlay.offset.y),
+            right: zoom.scale(canvas.left_offset + underlay.offset.x + width),
+            bottom: zoom.scale(canvas.top_offset + underlay.offset.y + height),
+        };
+        underlay.draw(hdc, underlay_rect);
+    }
+
+    // paint the reference drawing (which is never exported)
+    if state.reference_paths.len() > 0 {
+        let mut renderer = GdiRenderer::new(hdc, state.svg_options.fill_rule);
+        for path in &state.reference_paths {
+            renderer.begin_path();
+            trace_path(&mut renderer, path, canvas, zoom, true);
+            if path.closed {
+                renderer.close_figure();
+            }
+            renderer.stroke(REFERENCE_PATH_COLOR);
+        }
+    }
+
+    // paint the grid
+    render_grid(hdc, state, canvas, zoom, state.client_size, state.dark_mode);
+
+    // paint the symmetry axis
+    if state.symmetry {
+        let width = state.grid_count.x * canvas.horizontal_factor;
+        let height = state.grid_count.y * canvas.vertical_factor;
+        let (start, end) = match state.mirror_axis {
+            MirrorAxis::Vertical => (Point { x: width / 2, y: 0 }, Point { x: width / 2, y: height }),
+            MirrorAxis::Horizontal => (Point { x: 0, y: height / 2 }, Point { x: width, y: height / 2 }),
+        };
+        let _pen_guard = select_object_scoped(hdc, SYMMETRY_AXIS_PEN.handle(), "symmetry axis pen");
+        begin_path(hdc);
+        move_to(hdc, zoom.scale(canvas.left_offset + start.x), zoom.scale(canvas.top_offset + start.y));
+        line_to(hdc, zoom.scale(canvas.left_offset + end.x), zoom.scale(canvas.top_offset + end.y));
+        end_path(hdc);
+        stroke_path(hdc);
+    }
+
+    // paint existing paths
+    if state.antialias {
+        let mut renderer = AntialiasedRenderer::new(hdc, state.svg_options.fill_rule, OPEN_PATH_THICKNESS);
+        render_paths(&mut renderer, state, canvas, zoom, true);
+    } else {
+        render_paths(&mut GdiRenderer::new(hdc, state.svg_options.fill_rule), state, canvas, zoom, true);
+    }
+
+    // show how the path being drawn will be closed
+    if let (true, Some(path)) = (state.is_drawing, state.paths.last()) {
+        let subpaths = path.subpaths();
+        let current_subpath = subpaths.last().filter(|_| path.closed && !path.subpath_pending());
+        if let Some(subpath) = current_subpath.filter(|subpath| subpath.len() >= 2) {
+            let next_point = state.snapped_cursor();
+            let _pen_guard = select_object_scoped(hdc, CLOSING_PREVIEW_PEN.handle(), "closing preview pen");
+            begin_path(hdc);
+            move_to(
+                hdc,
+                zoom.scale(canvas.left_offset + next_point.x),
+                zoom.scale(canvas.top_offset + next_point.y),
+            );
+            line_to(
+                hdc,
+                zoom.scale(canvas.left_offset + subpath[0].x),
+                zoom.scale(canvas.top_offset + subpath[0].y),
+            );
+            end_path(hdc);
+            stroke_path(hdc);
+        }
+    }
+
+    // paint the export frame
+    if let Some(frame) = &state.svg_options.frame {
+        let left = zoom.scale(canvas.left_offset + frame.origin.x);
+        let top = zoom.scale(canvas.top_offset + frame.origin.y);
+        let right = zoom.scale(canvas.left_offset + frame.origin.x + frame.size.x);
+        let bottom = zoom.scale(canvas.top_offset + frame.origin.y + frame.size.y);
+        let _pen_guard = select_object_scoped(hdc, EXPORT_FRAME_PEN.handle(), "export frame pen");
+        begin_path(hdc);
+        move_to(hdc, left, top);
+        line_to(hdc, right, top);
+        line_to(hdc, right, bottom);
+        line_to(hdc, left, bottom);
+        close_figure(hdc);
+        end_path(hdc);
+        stroke_path(hdc);
+    }
+
+    // paint selected vertices
+    for &(path_index, point_index) in &state.selected_vertices {
+        let point = match state.paths.get(path_index).and_then(|path| path.points.get(point_index)) {
+            Some(&point) => point,
+            None => continue,
+        };
+        let marker_rect = RECT {
+            left: zoom.scale(canvas.left_offset + point.x - VERTEX_MARKER_SIZE/2),
+            top: zoom.scale(canvas.top_offset + point.y - VERTEX_MARKER_SIZE/2),
+            right: zoom.scale(canvas.left_offset + point.x + VERTEX_MARKER_SIZE/2),
+            bottom: zoom.scale(canvas.top_offset + point.y + VERTEX_MARKER_SIZE/2),
+        };
+        unsafe { FillRect(hdc, &marker_rect, SELECTION_BRUSH.handle()) };
+    }
+
+    // mark where the next line will start
+    if let (true, Some(&point)) = (state.is_drawing, state.paths.last().and_then(|path| path.points.last())) {
+        let marker_rect = RECT {
+            left: zoom.scale(canvas.left_offset + point.x - LAST_POINT_MARKER_SIZE/2),
+            top: zoom.scale(canvas.top_offset + point.y - LAST_POINT_MARKER_SIZE/2),
+            right: zoom.scale(canvas.left_offset + point.x + LAST_POINT_MARKER_SIZE/2),
+            bottom: zoom.scale(canvas.top_offset + point.y + LAST_POINT_MARKER_SIZE/2),
+        };
+        unsafe { FillRect(hdc, &marker_rect, LAST_POINT_BRUSH.handle()) };
+    }
+
+    // mark the control points of curves, which don't lie on the path
+    for path in &state.paths {
+        for point in path.control_points.iter().filter_map(|&i| path.points.get(i)) {
+            let marker_rect = RECT {
+                left: zoom.scale(canvas.left_offset + point.x - CONTROL_POINT_MARKER_SIZE/2),
+                top: zoom.scale(canvas.top_offset + point.y - CONTROL_POINT_MARKER_SIZE/2),
+                right: zoom.scale(canvas.left_offset + point.x + CONTROL_POINT_MARKER_SIZE/2),
+                bottom: zoom.scale(canvas.top_offset + point.y + CONTROL_POINT_MARKER_SIZE/2),
+            };
+            unsafe { FillRect(hdc, &marker_rect, CONTROL_POINT_BRUSH.handle()) };
+        }
+    }
+
+    // paint snap target
+    if state.snap_mode != SnapMode::Off && !state.cell_paint {
+        let target = state.snapped_cursor();
+        let _pen_guard = select_object_scoped(hdc, SNAP_PEN.handle(), "snap pen");
+        begin_path(hdc);
+        move_to(
+            hdc,
+            zoom.scale(canvas.left_offset + target.x - SNAP_MARKER_SIZE/2),
+            zoom.scale(canvas.top_offset + target.y - SNAP_MARKER_SIZE/2),
+        );
+        line_to(
+            hdc,
+            zoom.scale(canvas.left_offset + target.x + SNAP_MARKER_SIZE/2),
+            zoom.scale(canvas.top_offset + target.y - SNAP_MARKER_SIZE/2),
+        );
+        line_to(
+            hdc,
+            zoom.scale(canvas.left_offset + target.x + SNAP_MARKER_SIZE/2),
+            zoom.scale(canvas.top_offset + target.y + SNAP_MARKER_SIZE/2),
+        );
+        line_to(
+            hdc,
+            zoom.scale(canvas.left_offset + target.x - SNAP_MARKER_SIZE/2),
+            zoom.scale(canvas.top_offset + target.y + SNAP_MARKER_SIZE/2),
+        );
+        close_figure(hdc);
+        end_path(hdc);
+        stroke_path(hdc);
+    }
+
+    // paint cursor
+    let pen = if state.is_drawing {
+        crosshair_pen(state.crosshair_colors.drawing())
+    } else {
+        crosshair_pen(state.crosshair_colors.not_drawing())
+    };
+    select_object(hdc, pen, "crosshair pen");
+
+    // show the crosshair where a point would actually be placed
+    let crosshair = state.snapped_cursor();
+    let crosshair_length = state.crosshair_length.0;
+
+    // vertical line
+    begin_path(hdc);
+    move_to(
+        hdc,
+        zoom.scale(canvas.left_offset + crosshair.x),
+        zoom.scale(canvas.top_offset + crosshair.y - crosshair_length/2),
+    );
+    line_to(
+        hdc,
+        zoom.scale(canvas.left_offset + crosshair.x),
+        zoom.scale(canvas.top_offset + crosshair.y - crosshair_length/2 + crosshair_length),
+    );
+    end_path(hdc);
+    stroke_path(hdc);
+
+    // horizontal line
+    begin_path(hdc);
+    move_to(
+        hdc,
+        zoom.scale(canvas.left_offset + crosshair.x - crosshair_length/2),
+        zoom.scale(canvas.top_offset + crosshair.y),
+    );
+    line_to(
+        hdc,
+        zoom.scale(canvas.left_offset + crosshair.x - crosshair_length/2 + crosshair_length),
+        zoom.scale(canvas.top_offset + crosshair.y),
+    );
+    end_path(hdc);
+    stroke_path(hdc);
+
+    if state.show_log {
+        paint_log(hdc, state);
+    }
+    if state.show_help {
+        paint_help(hdc, state);
+    }
+}
+
+
+// only the lines within the given device-coordinate area
+fn render_grid(
+    hdc: HDC,
+    state: &ApplicationState,
+    canvas: CanvasConfig,
+    zoom: Zoom,
+    visible_size: Point,
+    dark_mode: bool,
+) {
+    if state.grid_count.x > 0 && state.grid_count.y > 0 {
+        let pen = match (state.solid_grid, dark_mode) {
+            (false, false) => GRID_PEN.handle(),
+            (true, false) => SOLID_GRID_PEN.handle(),
+            (false, true) => DARK_GRID_PEN.handle(),
+            (true, true) => DARK_SOLID_GRID_PEN.handle(),
+        };
+        select_object(hdc, pen, "grid pen");
+
+        // only paint the lines within the window, which keeps large grids fast
+        let grid_shift = state.grid_shift();
+        let x_length = state.grid_count.x * canvas.horizontal_factor;
+        let y_length = state.grid_count.y * canvas.vertical_factor;
+        let mut rows: Vec<i32> = visible_lines(
+            state.grid_count.y, canvas.top_offset + grid_shift.y, canvas.vertical_factor, visible_size.y, zoom,
+        )
+            .map(|y_index| grid_shift.y + y_index * canvas.vertical_factor)
+            .filter(|&y_pos| y_pos <= y_length)
+            .collect();
+        let mut columns: Vec<i32> = visible_lines(
+            state.grid_count.x, canvas.left_offset + grid_shift.x, canvas.horizontal_factor, visible_size.x, zoom,
+        )
+            .map(|x_index| grid_shift.x + x_index * canvas.horizontal_factor)
+            .filter(|&x_pos| x_pos <= x_length)
+            .collect();
+
+        // a shifted grid still has its outer lines along the edges of the canvas
+        if grid_shift.y != 0 {
+            rows.extend([0, y_length]);
+        }
+        if grid_shift.x != 0 {
+            columns.extend([0, x_length]);
         }
 
-        if redraw {
-            unsafe { RedrawWindow(handle, None, None, RDW_INVALIDATE | RDW_UPDATENOW) };
+        // horizontals
+        for y_pos in rows {
+            begin_path(hdc);
+            move_to(
+                hdc,
+                zoom.scale(canvas.left_offset),
+                zoom.scale(canvas.top_offset + y_pos),
+            );
+            line_to(
+                hdc,
+                zoom.scale(canvas.left_offset + x_length),
+                zoom.scale(canvas.top_offset + y_pos),
+            );
+            end_path(hdc);
+            stroke_path(hdc);
         }
-    }
 
-    default_window_proc(handle, message, wparam, lparam)
+        // verticals
+        for x_pos in columns {
+            begin_path(hdc);
+            move_to(
+                hdc,
+                zoom.scale(canvas.left_offset + x_pos),
+                zoom.scale(canvas.top_offset),
+            );
+            line_to(
+                hdc,
+                zoom.scale(canvas.left_offset + x_pos),
+                zoom.scale(canvas.top_offset + y_length),
+            );
+            end_path(hdc);
+            stroke_path(hdc);
+        }
+    }
 }
 
 
-fn scale(value: i32) -> i32 {
-    (value * RENDER_NUMERATOR) / RENDER_DENOMINATOR
+// plus one more on either side; all lines if the extent is not known yet
+fn visible_lines(count: i32, offset: i32, spacing: i32, extent: i32, zoom: Zoom) -> RangeInclusive<i32> {
+    if extent <= 0 {
+        return 0..=count;
+    }
+    let first = (zoom.unscale(0) - offset).div_euclid(spacing) - 1;
+    let last = (zoom.unscale(extent) - offset).div_euclid(spacing) + 1;
+    first.max(0)..=last.min(count)
 }
 
 
-fn paint_draw_window(handle: HWND) {
-    let mut paint_struct = PAINTSTRUCT::default();
-    let hdc = unsafe { BeginPaint(handle, &mut paint_struct) };
-    if hdc.is_invalid() {
-        return;
-    }
+// one figure per subpath; smoothing only applies if allowed and the path has no explicit curves
+fn trace_path<R: PathRenderer>(
+    renderer: &mut R,
+    path: &ClosedPath,
+    canvas: CanvasConfig,
+    zoom: Zoom,
+    allow_smoothing: bool,
+) {
+    for (subpath_index, range) in path.subpath_ranges().into_iter().enumerate() {
+        if subpath_index > 0 && path.closed {
+            renderer.close_figure();
+        }
 
-    // paint background
-    let background_brush: isize = (COLOR_WINDOW.0 + 1).try_into().unwrap();
-    unsafe { FillRect(hdc, &paint_struct.rcPaint, HBRUSH(background_brush)) };
+        let subpath = &path.points[range.clone()];
+        let spline = if path.smooth && path.control_points.len() == 0 && allow_smoothing {
+            cardinal_spline(subpath, path.tension_percent, path.closed)
+        } else {
+            Vec::new()
+        };
 
-    {
-        let state_guard = STATE.lock().expect("failed to lock state");
-
-        // paint the grid
-        if state_guard.grid_count.x > 0 && state_guard.grid_count.y > 0 {
-            select_object(hdc, *GRID_PEN, "grid pen");
-
-            // horizontals
-            let x_length = state_guard.grid_count.x * HORIZONTAL_FACTOR;
-            for y_index in 0..=state_guard.grid_count.y {
-                let y_pos = y_index * VERTICAL_FACTOR;
-                begin_path(hdc);
-                move_to(
-                    hdc,
-                    scale(LEFT_OFFSET),
-                    scale(TOP_OFFSET + y_pos),
-                );
-                line_to(
-                    hdc,
-                    scale(LEFT_OFFSET + x_length),
-                    scale(TOP_OFFSET + y_pos),
-                );
-                end_path(hdc);
-                stroke_path(hdc);
-            }
-
-            // verticals
-            let y_length = state_guard.grid_count.y * VERTICAL_FACTOR;
-            for x_index in 0..=state_guard.grid_count.x {
-                let x_pos = x_index * HORIZONTAL_FACTOR;
-                begin_path(hdc);
-                move_to(
-                    hdc,
-                    scale(LEFT_OFFSET + x_pos),
-                    scale(TOP_OFFSET),
-                );
-                line_to(
-                    hdc,
-                    scale(LEFT_OFFSET + x_pos),
-                    scale(TOP_OFFSET + y_length),
-                );
-                end_path(hdc);
-                stroke_path(hdc);
+        renderer.move_to(
+            zoom.scale(canvas.left_offset + subpath[0].x),
+            zoom.scale(canvas.top_offset + subpath[0].y),
+        );
+        if spline.len() > 0 {
+            let bezier_points: Vec<POINT> = spline.iter()
+                .flat_map(|segment| [segment.control1, segment.control2, segment.end])
+                .map(|point| POINT {
+                    x: zoom.scale(canvas.left_offset + point.x),
+                    y: zoom.scale(canvas.top_offset + point.y),
+                })
+                .collect();
+            renderer.poly_bezier_to(&bezier_points);
+        } else {
+            let mut current = subpath[0];
+            for segment in path.segments(range) {
+                if let Segment::Line(point) = segment {
+                    renderer.line_to(
+                        zoom.scale(canvas.left_offset + point.x),
+                        zoom.scale(canvas.top_offset + point.y),
+                    );
+                } else {
+                    let cubic = segment.to_cubic(current);
+                    let bezier_points = [cubic.control1, cubic.control2, cubic.end]
+                        .map(|point| POINT {
+                            x: zoom.scale(canvas.left_offset + point.x),
+                            y: zoom.scale(canvas.top_offset + point.y),
+                        });
+                    renderer.poly_bezier_to(&bezier_points);
+                }
+                current = segment.end();
             }
         }
+    }
+}
 
-        // paint existing paths
-        select_object(hdc, *FONT_BRUSH, "font brush");
 
-        for (path_index, path) in state_guard.paths.iter().enumerate() {
-            if path.points.len() == 0 {
-                continue;
-            }
+// in interactive mode, the selected path is highlighted and the path being drawn is extended to
+// the cursor; otherwise, the drawing is painted as it would be exported
+fn render_paths<R: PathRenderer>(
+    renderer: &mut R,
+    state: &ApplicationState,
+    canvas: CanvasConfig,
+    zoom: Zoom,
+    interactive: bool,
+) {
+    let self_intersecting = if interactive {
+        self_intersecting_paths(&state.paths)
+    } else {
+        Vec::new()
+    };
+    for (path_index, path) in state.paths.iter().enumerate() {
+        if path.points.len() == 0 {
+            continue;
+        }
 
-            begin_path(hdc);
-            move_to(
-                hdc,
-                scale(LEFT_OFFSET + path.points[0].x),
-                scale(TOP_OFFSET + path.points[0].y),
+        let is_selected = interactive && !state.edit_mode && state.selected_paths.contains(&path_index);
+        let color = if is_selected {
+            SELECTION_COLOR
+        } else if interactive && state.dark_mode && path.color == BLACK.0 {
+            // black paths would vanish against the dark background
+            DARK_MODE_PATH_COLOR
+        } else {
+            COLORREF(path.color)
+        };
+
+        // paths being drawn are never smoothed
+        let is_active = interactive
+            && state.is_drawing
+            && (path_index == state.paths.len() - 1 || state.mirror_path_index == Some(path_index));
+        renderer.begin_path();
+        trace_path(renderer, path, canvas, zoom, !is_active);
+
+        if is_active && path.subpath_pending() {
+            // the next point begins a new subpath; don't connect it to the previous one
+        } else if interactive && state.is_drawing && path_index == state.paths.len() - 1 {
+            // also draw a line to where the next point would land
+            let next_point = state.snapped_cursor();
+            renderer.line_to(
+                zoom.scale(canvas.left_offset + next_point.x),
+                zoom.scale(canvas.top_offset + next_point.y),
             );
-            for point in path.points.iter().skip(1) {
-                line_to(
-                    hdc,
-                    scale(LEFT_OFFSET + point.x),
-                    scale(TOP_OFFSET + point.y),
-                );
+        } else if interactive && state.is_drawing && state.mirror_path_index == Some(path_index) {
+            // mirror the line to where the next point would land
+            let next_point = state.snapped_cursor()
+                .mirrored(state.mirror_axis, state.grid_count, canvas);
+            renderer.line_to(
+                zoom.scale(canvas.left_offset + next_point.x),
+                zoom.scale(canvas.top_offset + next_point.y),
+            );
+        }
+
+        if path.closed {
+            renderer.close_figure();
+            if interactive && state.outline_preview {
+                renderer.stroke(color);
+            } else if is_active {
+                // keep the grid and the points underneath visible until the path is finished
+                renderer.fill_translucent(color, DRAWING_FILL_OPACITY);
+            } else {
+                renderer.fill(color);
             }
 
-            if state_guard.is_drawing && path_index == state_guard.paths.len() - 1 {
-                // also draw a line to the cursor
-                line_to(
-                    hdc,
-                    scale(LEFT_OFFSET + state_guard.cursor.x),
-                    scale(TOP_OFFSET + state_guard.cursor.y),
-                );
+            if !is_active && self_intersecting.get(path_index) == Some(&true) {
+                // crossing edges fill in surprising ways; outline the path as a warning
+                renderer.begin_path();
+                trace_path(renderer, path, canvas, zoom, true);
+                renderer.close_figure();
+                renderer.stroke(INTERSECTION_COLOR);
             }
+        } else {
+            renderer.stroke(color);
+        }
+    }
+}
 
-            close_figure(hdc);
-            end_path(hdc);
-            fill_path(hdc);
+
+fn paint_log(hdc: HDC, state: &ApplicationState) {
+    let px = |value| dpi_scale(value, state.effective_dpi());
+    let visible_count = state.log.len().min(LOG_VISIBLE_LINES);
+    let line_count: i32 = visible_count.try_into().unwrap();
+    let panel_rect = RECT {
+        left: px(LOG_MARGIN),
+        top: px(LOG_MARGIN),
+        right: px(LOG_MARGIN + LOG_PANEL_WIDTH),
+        bottom: px(LOG_MARGIN + (line_count.max(1) * LOG_LINE_HEIGHT) + 2*LOG_MARGIN),
+    };
+    unsafe { FillRect(hdc, &panel_rect, LOG_BACKGROUND_BRUSH.handle()) };
+
+    let _font_guard = select_object_scoped(hdc, log_font(state.effective_dpi()), "log font");
+    unsafe { SetBkMode(hdc, TRANSPARENT) };
+    unsafe { SetTextColor(hdc, LOG_TEXT_COLOR) };
+
+    let first_visible = state.log.len() - visible_count;
+    for (i, message) in state.log.iter().skip(first_visible).enumerate() {
+        let line_index: i32 = i.try_into().unwrap();
+        let message_wide: Vec<u16> = message.encode_utf16().collect();
+        unsafe {
+            TextOutW(
+                hdc,
+                px(2*LOG_MARGIN),
+                px(2*LOG_MARGIN + line_index * LOG_LINE_HEIGHT),
+                &message_wide,
+            )
+        };
+    }
+}
+
+
+fn paint_help(hdc: HDC, state: &ApplicationState) {
+    let px = |value| dpi_scale(value, state.effective_dpi());
+    let line_count: i32 = KEY_BINDINGS.len().try_into().unwrap();
+    let panel_rect = RECT {
+        left: px(HELP_LEFT),
+        top: px(LOG_MARGIN),
+        right: px(HELP_LEFT + HELP_PANEL_WIDTH),
+        bottom: px(LOG_MARGIN + (line_count * LOG_LINE_HEIGHT) + 2*LOG_MARGIN),
+    };
+    fill_rect_translucent(hdc, &panel_rect, LOG_BACKGROUND_BRUSH.handle(), HELP_OPACITY);
+
+    let _font_guard = select_object_scoped(hdc, log_font(state.effective_dpi()), "help font");
+    unsafe { SetBkMode(hdc, TRANSPARENT) };
+    unsafe { SetTextColor(hdc, LOG_TEXT_COLOR) };
+
+    for (i, (key, action)) in KEY_BINDINGS.iter().enumerate() {
+        let line_index: i32 = i.try_into().unwrap();
+        let y = px(2*LOG_MARGIN + line_index * LOG_LINE_HEIGHT);
+        let key_wide: Vec<u16> = key.encode_utf16().collect();
+        let action_wide: Vec<u16> = action.encode_utf16().collect();
+        unsafe { TextOutW(hdc, px(HELP_LEFT + LOG_MARGIN), y, &key_wide) };
+        unsafe { TextOutW(hdc, px(HELP_LEFT + LOG_MARGIN + HELP_KEY_COLUMN_WIDTH), y, &action_wide) };
+    }
+}
+
+
+fn on_off(value: bool) -> &'static str {
+    if value { "on" } else { "off" }
+}
+
+
+fn enforce_path_limits(state: &mut ApplicationState, path_count_before: usize) {
+    if let Some(limit) = state.path_limits.hard_limit {
+        if state.paths.len() > limit {
+            state.paths.truncate(limit);
+            state.is_drawing = false;
+            state.mirror_path_index = None;
+            state.log(format!("the document cannot have more than {} paths", limit));
         }
+    }
 
-        // paint cursor
-        let pen = if state_guard.is_drawing { *DRAWING_CROSSHAIR_PEN } else { *NOT_DRAWING_CROSSHAIR_PEN };
-        select_object(hdc, pen, "crosshair pen");
+    let warning = state.path_limits.warning;
+    if path_count_before <= warning && state.paths.len() > warning {
+        state.log(format!(
+            "the document has more than {} paths; consider splitting it up or exporting it",
+            warning,
+        ));
+    }
+}
 
-        // vertical line
-        begin_path(hdc);
-        move_to(
-            hdc,
-            scale(LEFT_OFFSET + state_guard.cursor.x),
-            scale(TOP_OFFSET + state_guard.cursor.y - CROSSHAIR_LENGTH/2),
-        );
-        line_to(
-            hdc,
-            scale(LEFT_OFFSET + state_guard.cursor.x),
-            scale(TOP_OFFSET + state_guard.cursor.y - CROSSHAIR_LENGTH/2 + CROSSHAIR_LENGTH),
-        );
-        end_path(hdc);
-        stroke_path(hdc);
 
-        // horizontal line
-        begin_path(hdc);
-        move_to(
-            hdc,
-            scale(LEFT_OFFSET + state_guard.cursor.x - CROSSHAIR_LENGTH/2),
-            scale(TOP_OFFSET + state_guard.cursor.y),
-        );
-        line_to(
-            hdc,
-            scale(LEFT_OFFSET + state_guard.cursor.x - CROSSHAIR_LENGTH/2 + CROSSHAIR_LENGTH),
-            scale(TOP_OFFSET + state_guard.cursor.y),
-        );
-        end_path(hdc);
-        stroke_path(hdc);
+fn window_title(state: &ApplicationState) -> String {
+    // the coordinates are counted from the grid origin
+    let grid_shift = state.grid_shift();
+    let mut title = format!(
+        "{}PixelPath \u{2014} ({}, {}) \u{2014} grid {}\u{D7}{} \u{2014} snap: {}",
+        if state.dirty { "*" } else { "" },
+        state.cursor.x - grid_shift.x, state.cursor.y - grid_shift.y,
+        state.grid_count.x, state.grid_count.y,
+        state.snap_mode.name(),
+    );
+    if state.edit_mode {
+        title.push_str(" \u{2014} editing");
+    }
+    if state.adjusting_underlay {
+        title.push_str(" \u{2014} adjusting reference image");
+    }
+    let intersecting_count = self_intersecting_paths(&state.paths)
+        .into_iter()
+        .filter(|intersects| *intersects)
+        .count();
+    if intersecting_count > 0 {
+        write!(title, " \u{2014} {} self-intersecting", intersecting_count).unwrap();
+    }
+    let measured_path = state.selected_path_index()
+        .and_then(|path_index| state.paths.get(path_index))
+        .filter(|path| path.points.len() > 1);
+    if let Some(path) = measured_path {
+        // measured in grid cells
+        let perimeter = path.perimeter_in_cells(state.canvas);
+        if path.closed {
+            let area = path.area_in_cells(state.canvas);
+            write!(title, " \u{2014} area {:.2}, perimeter {:.2}", area, perimeter).unwrap();
+        } else {
+            write!(title, " \u{2014} length {:.2}", perimeter).unwrap();
+        }
+    }
+    if let Some(entry) = &state.coordinate_entry {
+        write!(title, " \u{2014} go to: {}_", entry).unwrap();
+    }
+    if state.paths.len() > state.path_limits.warning {
+        write!(title, " \u{2014} {} paths (rendering may be slow)", state.paths.len()).unwrap();
+    }
+    title
+}
+
+
+fn set_window_title(handle: HWND, title: &str) {
+    let title_wide: Vec<u16> = title.encode_utf16()
+        .chain(std::iter::once(0x0000))
+        .collect();
+    let _ = unsafe { SetWindowTextW(handle, PCWSTR(title_wide.as_ptr())) };
+}
+
+
+fn open_file(parent: HWND, filter: PCWSTR) -> Option<PathBuf> {
+    let mut path_buf = vec![0u16; 32768];
+
+    let mut open_file_name = OPENFILENAMEW::default();
+    open_file_name.lStructSize = std::mem::size_of_val(&open_file_name).try_into().unwrap();
+    open_file_name.hwndOwner = parent;
+    open_file_name.lpstrFilter = filter;
+    open_file_name.lpstrFile = PWSTR(path_buf.as_mut_ptr());
+    open_file_name.nMaxFile = path_buf.len().try_into().unwrap();
+    open_file_name.Flags = OFN_FILEMUSTEXIST | OFN_PATHMUSTEXIST;
+    let result = unsafe { GetOpenFileNameW(&mut open_file_name) };
+    if !result.as_bool() {
+        return None;
     }
 
-    unsafe { EndPaint(handle, &paint_struct) };
+    let nul_index = path_buf.iter()
+        .position(|c| *c == 0x0000)
+        .unwrap_or(path_buf.len());
+    Some(PathBuf::from(OsString::from_wide(&path_buf[0..nul_index])))
 }
 
 
-fn save_svg(parent: HWND, svg_string: &str) {
+// starts out in the directory of the previously chosen file, if any
+fn save_file_name(parent: HWND, filter: PCWSTR, default_extension: PCWSTR) -> Option<PathBuf> {
     let mut path_buf = vec![0u16; 32768];
+    // must outlive the dialog; empty if this is the first time
+    let initial_dir_wide: Vec<u16> = LAST_SAVE_DIRECTORY.lock().unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .map(|dir| dir.as_os_str().encode_wide().chain(std::iter::once(0x0000)).collect())
+        .unwrap_or_default();
 
     let mut open_file_name = OPENFILENAMEW::default();
     open_file_name.lStructSize = std::mem::size_of_val(&open_file_name).try_into().unwrap();
     open_file_name.hwndOwner = parent;
-    open_file_name.lpstrFilter = w!("Scalable Vector Graphics (*.svg)\0*.svg\0All Files (*.*)\0*.*\0\0");
-    open_file_name.lpstrDefExt = w!("svg");
+    open_file_name.lpstrFilter = filter;
+    open_file_name.lpstrDefExt = default_extension;
     open_file_name.lpstrFile = PWSTR(path_buf.as_mut_ptr());
     open_file_name.nMaxFile = path_buf.len().try_into().unwrap();
+    if initial_dir_wide.len() > 0 {
+        open_file_name.lpstrInitialDir = PCWSTR(initial_dir_wide.as_ptr());
+    }
     open_file_name.Flags = OFN_OVERWRITEPROMPT;
     let result = unsafe { GetSaveFileNameW(&mut open_file_name) };
     if !result.as_bool() {
-        return;
+        return None;
     }
 
     let nul_index = path_buf.iter()
         .position(|c| *c == 0x0000)
         .unwrap_or(path_buf.len());
-    let path_osstring = OsString::from_wide(&path_buf[0..nul_index]);
-    if let Err(e) = std::fs::write(&path_osstring, svg_string) {
-        println!("error writing SVG: {}", e);
+    let path = PathBuf::from(OsString::from_wide(&path_buf[0..nul_index]));
+    if let Some(dir) = path.parent() {
+        *LAST_SAVE_DIRECTORY.lock().unwrap_or_else(PoisonError::into_inner) = Some(dir.to_path_buf());
+    }
+    Some(path)
+}
+
+
+// the state must not be locked; failures are logged
+fn open_drawing(path: PathBuf) {
+    // SVG files exported earlier are laid out on the current grid cells
+    let is_svg = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("svg"));
+    let loaded = if is_svg {
+        let (canvas, svg_options) = {
+            let state_guard = lock_state();
+            (state_guard.canvas, state_guard.svg_options)
+        };
+        load_svg(&path, canvas, svg_options)
+    } else {
+        load_project(&path)
+    };
+    let mut state_guard = lock_state();
+    match loaded {
+        Ok(project) => {
+            // take over the drawing, but keep the editor settings
+            let state = &mut *state_guard;
+            state.paths = project.paths;
+            state.grid_count = state.grid_limit.apply(project.grid_count);
+            if state.grid_count != project.grid_count {
+                let message = format!("reduced the grid to the limit of {}\u{D7}{} cells", state.grid_limit.0.x, state.grid_limit.0.y);
+                state.log(message);
+            }
+            state.canvas = project.canvas;
+            state.grid_origin = project.grid_origin;
+            state.svg_options = project.svg_options;
+            state.is_drawing = false;
+            state.mirror_path_index = None;
+            state.selected_vertices.clear();
+            state.vertex_cursor = None;
+            state.reset_history();
+            state.dirty = false;
+            if is_svg {
+                // saving as SVG suggests overwriting the file
+                state.current_file = Some(path.clone());
+                state.log(format!("opened SVG {}", path.display()));
+            } else {
+                state.log(format!("opened project {}", path.display()));
+            }
+        },
+        Err(e) => {
+            state_guard.log(format!("error opening project {}: {}", path.display(), e));
+        },
+    }
+}
+
+
+// the state must not be locked; returns whether the drawing may be thrown away
+fn may_discard_changes(parent: HWND, question: PCWSTR) -> bool {
+    let dirty = lock_state().dirty;
+    if !dirty {
+        return true;
+    }
+    let answer = unsafe { MessageBoxW(parent, question, w!("PixelPath"), MB_YESNOCANCEL | MB_ICONWARNING) };
+    answer != IDCANCEL && (answer != IDYES || save_drawing(parent))
+}
+
+
+// the state must not be locked; returns whether the drawing was saved
+fn save_drawing(parent: HWND) -> bool {
+    let path = match save_file_name(parent, PROJECT_FILTER, w!("pixelpath")) {
+        Some(p) => p,
+        None => return false,
+    };
+
+    let mut state_guard = lock_state();
+    match save_project(&path, &state_guard) {
+        Ok(()) => {
+            state_guard.log(format!("saved project to {}", path.display()));
+            state_guard.dirty = false;
+            true
+        },
+        Err(e) => {
+            let message = format!("error saving project to {}: {}", path.display(), e);
+            state_guard.log(message.clone());
+            drop(state_guard);
+            show_error(parent, &message);
+            false
+        },
+    }
+}
+
+
+// in addition to logging; the state must not be locked, as the message box keeps handling the
+// window's messages
+fn show_error(parent: HWND, message: &str) {
+    let message_wide: Vec<u16> = message.encode_utf16()
+        .chain(std::iter::once(0x0000))
+        .collect();
+    unsafe { MessageBoxW(parent, PCWSTR(message_wide.as_ptr()), w!("PixelPath"), MB_OK | MB_ICONERROR) };
+}
+
+
+// for when there is no window to log it in
+fn show_info(parent: HWND, message: &str) {
+    let message_wide: Vec<u16> = message.encode_utf16()
+        .chain(std::iter::once(0x0000))
+        .collect();
+    unsafe { MessageBoxW(parent, PCWSTR(message_wide.as_ptr()), w!("PixelPath"), MB_OK | MB_ICONINFORMATION) };
+}
+
+
+// `None` if the user cancelled
+fn save_svg(parent: HWND, svg_string: &str) -> Option<(PathBuf, std::io::Result<()>)> {
+    let filter = w!("Scalable Vector Graphics (*.svg)\0*.svg\0All Files (*.*)\0*.*\0\0");
+    let path = save_file_name(parent, filter, w!("svg"))?;
+    let written = std::fs::write(&path, svg_string);
+    Some((path, written))
+}
+
+
+// at the true coordinates
+fn export_emf(path: &Path, state: &ApplicationState) -> windows::core::Result<()> {
+    let path_wide: Vec<u16> = path.as_os_str().encode_wide()
+        .chain(std::iter::once(0x0000))
+        .collect();
+    let hdc = unsafe {
+        CreateEnhMetaFileW(None, PCWSTR(path_wide.as_ptr()), None, w!("PixelPath\0Drawing\0\0"))
+    };
+    if hdc.is_invalid() {
+        return Err(windows::core::Error::from_win32());
+    }
+
+    // paint the same way as on screen, but without offset, zoom or editing aids
+    let canvas = CanvasConfig {
+        left_offset: 0,
+        top_offset: 0,
+        ..state.canvas
+    };
+    {
+        let _pen_guard = preserve_selection(hdc, OBJ_PEN);
+        let _brush_guard = preserve_selection(hdc, OBJ_BRUSH);
+        let mut renderer = GdiRenderer::new(hdc, state.svg_options.fill_rule);
+        render_paths(&mut renderer, state, canvas, Zoom { numerator: 1, denominator: 1 }, false);
+    }
+
+    let metafile = unsafe { CloseEnhMetaFile(hdc) };
+    if metafile.is_invalid() {
+        return Err(windows::core::Error::from_win32());
+    }
+    unsafe { DeleteEnhMetaFile(metafile) };
+    Ok(())
+}
+
+
+// as large as fits on the page; the state must not be locked; `Ok(false)` if the user cancelled
+fn print_drawing(parent: HWND) -> windows::core::Result<bool> {
+    let mut print_dialog = PRINTDLGW::default();
+    print_dialog.lStructSize = std::mem::size_of_val(&print_dialog).try_into().unwrap();
+    print_dialog.hwndOwner = parent;
+    print_dialog.Flags = PD_RETURNDC | PD_NOPAGENUMS | PD_NOSELECTION;
+    let chosen = unsafe { PrintDlgW(&mut print_dialog) };
+
+    // only the device context is needed
+    for settings in [print_dialog.hDevMode, print_dialog.hDevNames] {
+        if !settings.is_invalid() {
+            let _ = unsafe { GlobalFree(settings) };
+        }
+    }
+    if !chosen.as_bool() {
+        return Ok(false);
+    }
+
+    let printer_dc = print_dialog.hDC;
+    let printed = {
+        let state_guard = lock_state();
+        print_page(printer_dc, &state_guard)
+    };
+    unsafe { DeleteDC(printer_dc) };
+    printed.map(|()| true)
+}
+
+
+fn print_page(printer_dc: HDC, state: &ApplicationState) -> windows::core::Result<()> {
+    // printers have far more pixels per inch than displays, so size everything by the page
+    let page = Point {
+        x: unsafe { GetDeviceCaps(printer_dc, HORZRES) },
+        y: unsafe { GetDeviceCaps(printer_dc, VERTRES) },
+    };
+    let content = Point {
+        x: state.grid_count.x * state.canvas.horizontal_factor,
+        y: state.grid_count.y * state.canvas.vertical_factor,
+    };
+    let zoom = Zoom::fitting(content, page);
+    let canvas = CanvasConfig {
+        // center the grid on the page
+        left_offset: zoom.unscale((page.x - zoom.scale(content.x)) / 2),
+        top_offset: zoom.unscale((page.y - zoom.scale(content.y)) / 2),
+        ..state.canvas
+    };
+
+    let doc_info = DOCINFOW {
+        cbSize: std::mem::size_of::<DOCINFOW>().try_into().unwrap(),
+        lpszDocName: w!("PixelPath drawing"),
+        ..DOCINFOW::default()
+    };
+    if unsafe { StartDocW(printer_dc, &doc_info) } <= 0 {
+        return Err(windows::core::Error::from_win32());
+    }
+    if unsafe { StartPage(printer_dc) } <= 0 {
+        let error = windows::core::Error::from_win32();
+        unsafe { AbortDoc(printer_dc) };
+        return Err(error);
+    }
+    {
+        let _pen_guard = preserve_selection(printer_dc, OBJ_PEN);
+        let _brush_guard = preserve_selection(printer_dc, OBJ_BRUSH);
+        render_grid(printer_dc, state, canvas, zoom, page, false);
+        render_paths(&mut GdiRenderer::new(printer_dc, state.svg_options.fill_rule), state, canvas, zoom, false);
+    }
+    if unsafe { EndPage(printer_dc) } <= 0 {
+        let error = windows::core::Error::from_win32();
+        unsafe { AbortDoc(printer_dc) };
+        return Err(error);
+    }
+    if unsafe { EndDoc(printer_dc) } <= 0 {
+        return Err(windows::core::Error::from_win32());
+    }
+    Ok(())
+}
+
+
+// at the current zoom level
+fn export_png(path: &Path, state: &ApplicationState) -> image::ImageResult<()> {
+    let to_image_error = |e: windows::core::Error| {
+        image::ImageError::IoError(std::io::Error::other(e))
+    };
+    let zoom = state.zoom;
+    let canvas = CanvasConfig {
+        left_offset: 0,
+        top_offset: 0,
+        ..state.canvas
+    };
+
+    // one more pixel in each direction to fit the last grid line; in i64, as the size of a huge
+    // grid does not fit into an i32
+    let scaled_size = |cells: i32, factor: i32| {
+        i64::from(cells) * i64::from(factor) * i64::from(zoom.numerator) / i64::from(zoom.denominator) + 1
+    };
+    let full_width = scaled_size(state.grid_count.x, canvas.horizontal_factor);
+    let full_height = scaled_size(state.grid_count.y, canvas.vertical_factor);
+    let too_large = || image::ImageError::IoError(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("an image of {}\u{D7}{} pixels is too large", full_width, full_height),
+    ));
+    let (width, height) = match (i32::try_from(full_width), i32::try_from(full_height)) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => return Err(too_large()),
+    };
+
+    // four bytes per pixel; refuse sizes whose buffer could not even be addressed
+    let byte_count = usize::try_from(width).ok()
+        .zip(usize::try_from(height).ok())
+        .and_then(|(w, h)| w.checked_mul(h))
+        .and_then(|pixel_count| pixel_count.checked_mul(4))
+        .ok_or_else(too_large)?;
+
+    let mut bitmap_info = BITMAPINFO::default();
+    bitmap_info.bmiHeader.biSize = std::mem::size_of::<BITMAPINFOHEADER>().try_into().unwrap();
+    bitmap_info.bmiHeader.biWidth = width;
+    bitmap_info.bmiHeader.biHeight = -height; // top-down
+    bitmap_info.bmiHeader.biPlanes = 1;
+    bitmap_info.bmiHeader.biBitCount = 32;
+    bitmap_info.bmiHeader.biCompression = BI_RGB.0;
+
+    let memory_dc = unsafe { CreateCompatibleDC(None) };
+    let mut bits: *mut c_void = std::ptr::null_mut();
+    let bitmap = match unsafe { CreateDIBSection(memory_dc, &bitmap_info, DIB_RGB_COLORS, &mut bits, None, 0) } {
+        Ok(b) => b,
+        Err(e) => {
+            unsafe { DeleteDC(memory_dc) };
+            return Err(to_image_error(e));
+        },
+    };
+    let previous = unsafe { SelectObject(memory_dc, bitmap) };
+
+    // GDI ignores the alpha channel, so paint onto an opaque background
+    let background_rect = RECT {
+        left: 0,
+        top: 0,
+        right: width,
+        bottom: height,
+    };
+    unsafe { FillRect(memory_dc, &background_rect, EXPORT_BACKGROUND_BRUSH.handle()) };
+    {
+        let _pen_guard = preserve_selection(memory_dc, OBJ_PEN);
+        let _brush_guard = preserve_selection(memory_dc, OBJ_BRUSH);
+        render_grid(memory_dc, state, canvas, zoom, Point { x: width, y: height }, false);
+        render_paths(&mut GdiRenderer::new(memory_dc, state.svg_options.fill_rule), state, canvas, zoom, false);
+    }
+    unsafe { GdiFlush() };
+
+    // BGRx to RGBA
+    let bgrx = unsafe { std::slice::from_raw_parts(bits as *const u8, byte_count) };
+    let mut rgba = Vec::with_capacity(bgrx.len());
+    for pixel in bgrx.chunks(4) {
+        rgba.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 0xFF]);
     }
+
+    unsafe { SelectObject(memory_dc, previous) };
+    unsafe { DeleteDC(memory_dc) };
+    unsafe { DeleteObject(bitmap) };
+
+    image::save_buffer_with_format(
+        path,
+        &rgba,
+        width.try_into().unwrap(),
+        height.try_into().unwrap(),
+        image::ColorType::Rgba8,
+        image::ImageFormat::Png,
+    )
 }
 
 
@@ -357,6 +3160,65 @@ fn main() {
     let mut startup_info = STARTUPINFOW::default();
     unsafe { GetStartupInfoW(&mut startup_info) };
 
+    // draw crisply on high-DPI displays instead of being scaled up by Windows
+    // (fails on older versions of Windows, which then keep scaling the window)
+    let _ = unsafe { SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) };
+
+    let arguments = match parse_arguments(std::env::args_os().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(e) => {
+            // a console is not necessarily being watched when launched from Explorer
+            show_error(HWND::default(), &format!("{}\n\n{}", e, USAGE));
+            return;
+        },
+    };
+    if let Some(change) = arguments.file_type_change {
+        let (changed, done) = match change {
+            FileTypeChange::Register => (register_file_types(), "registered"),
+            FileTypeChange::Unregister => (unregister_file_types(), "unregistered"),
+        };
+        match changed {
+            Ok(()) => show_info(HWND::default(), &format!("{} the PixelPath file types", done)),
+            Err(e) => show_error(HWND::default(), &format!("failed to change the file types: {}", e)),
+        }
+        return;
+    }
+
+    if arguments.no_session {
+        lock_state().session_disabled = true;
+    } else if let Some(mut session_state) = load_session() {
+        // resume where the previous session left off
+        prune_selection(&mut session_state);
+        session_state.reset_history();
+        session_state.log("restored previous session");
+        let mut state_guard = lock_state();
+        *state_guard = session_state;
+    }
+
+    // the command line takes precedence over the session
+    if let Some(grid_limit) = arguments.grid_limit {
+        // also applies to the grid of the opened file
+        lock_state().grid_limit = GridLimit(grid_limit);
+    }
+    if let Some(file) = arguments.file {
+        open_drawing(file);
+    }
+    let mut state_guard = lock_state();
+    if let Some(warning) = arguments.path_warning {
+        state_guard.path_limits.warning = warning;
+    }
+    if let Some(hard_limit) = arguments.path_limit {
+        state_guard.path_limits.hard_limit = Some(hard_limit);
+    }
+    if let Some(grid_count) = arguments.grid_count {
+        state_guard.grid_count = state_guard.grid_limit.apply(grid_count);
+        if state_guard.grid_count != grid_count {
+            let message = format!("reduced the grid to the limit of {}\u{D7}{} cells", state_guard.grid_limit.0.x, state_guard.grid_limit.0.y);
+            state_guard.log(message);
+        }
+    }
+    drop(state_guard);
+
     // register a class for our window
     let window_class_name = w!("PixelPathDrawWindow");
     let mut window_class = WNDCLASSW::default();
@@ -388,6 +3250,7 @@ fn main() {
     if window_handle.0 == 0 {
         panic!("failed to create window: {}", windows::core::Error::from_win32());
     }
+    lock_state().dpi = unsafe { GetDpiForWindow(window_handle) };
 
     unsafe { ShowWindow(window_handle, SW_SHOWDEFAULT) };
 
@@ -402,3 +3265,143 @@ fn main() {
         unsafe { DispatchMessageW(&message) };
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drawing_without_a_path_starts_a_new_one() {
+        // the path being drawn has vanished, e.g. by undoing its creation
+        let mut state = ApplicationState {
+            is_drawing: true,
+            cursor: Point { x: 100, y: 200 },
+            ..ApplicationState::default()
+        };
+        state.start_subpath();
+        state.drop_point();
+        assert_eq!(state.paths.len(), 1);
+        assert_eq!(state.paths[0].points, vec![Point { x: 100, y: 200 }]);
+        assert_eq!(state.paths[0].subpath_starts, Vec::<usize>::new());
+        assert!(state.is_drawing);
+
+        // a vanished mirror image is skipped
+        state.mirror_path_index = Some(5);
+        state.cursor = Point { x: 300, y: 200 };
+        state.drop_point();
+        state.finish_open_path();
+        assert_eq!(state.paths.len(), 1);
+        assert_eq!(state.paths[0].points.len(), 2);
+        assert!(!state.paths[0].closed);
+        assert!(!state.is_drawing);
+    }
+
+    #[test]
+    fn resumed_path_takes_new_points_until_finished() {
+        let point = |x, y| Point { x, y };
+        let first = ClosedPath { points: vec![point(0, 0), point(100, 0)], ..ClosedPath::default() };
+        let second = ClosedPath { points: vec![point(0, 300), point(100, 300)], ..ClosedPath::default() };
+        let mut state = ApplicationState {
+            paths: vec![first.clone(), second.clone()],
+            cursor: point(100, 100),
+            ..ApplicationState::default()
+        };
+        state.reset_history();
+        let original = state.undo_snapshot();
+
+        state.selected_paths.insert(0);
+        let before = state.undo_marker();
+        state.resume_path();
+        state.record_undo(before);
+        let resumed = state.undo_snapshot();
+        assert!(state.is_drawing);
+        assert_eq!(state.paths, vec![second.clone(), first.clone()]);
+
+        let before = state.undo_marker();
+        state.drop_point();
+        state.record_undo(before);
+        assert_eq!(state.paths[1].points, vec![point(0, 0), point(100, 0), point(100, 100)]);
+
+        // undo goes back through the point to before the path was resumed
+        state.undo();
+        assert_eq!(state.undo_snapshot(), resumed);
+        state.undo();
+        assert_eq!(state.undo_snapshot(), original);
+        state.redo();
+        state.redo();
+        assert_eq!(state.paths[1].points.len(), 3);
+
+        // once finished, the next point starts a new path
+        state.finish_path();
+        state.cursor = point(300, 300);
+        state.drop_point();
+        assert_eq!(state.paths.len(), 3);
+        assert_eq!(state.paths[2].points, vec![point(300, 300)]);
+    }
+
+    #[test]
+    fn poisoned_state_is_recovered() {
+        let panicked = std::thread::spawn(|| {
+            let mut state_guard = lock_state();
+            state_guard.cursor = Point { x: 300, y: 100 };
+            panic!("panicking while the state is locked");
+        }).join();
+        assert!(panicked.is_err());
+
+        let mut state_guard = lock_state();
+        assert!(!STATE.is_poisoned());
+        assert_eq!(state_guard.cursor, Point { x: 300, y: 100 });
+        assert!(state_guard.log.back().unwrap().contains("recovered"));
+        state_guard.drop_point();
+        assert_eq!(state_guard.paths.last().unwrap().points, vec![Point { x: 300, y: 100 }]);
+    }
+
+    #[test]
+    fn history_without_steps_is_left_alone() {
+        let mut state = ApplicationState::default();
+        state.undo();
+        state.redo();
+        assert_eq!(state.undo_snapshot(), ApplicationState::default().undo_snapshot());
+        assert!(!state.dirty);
+    }
+
+    #[test]
+    fn undo_and_redo_round_trip_dropped_points() {
+        let mut state = ApplicationState::default();
+        let mut states = vec![state.undo_snapshot()];
+        for cursor in [Point { x: 0, y: 0 }, Point { x: 200, y: 0 }, Point { x: 200, y: 200 }] {
+            let before = state.undo_marker();
+            state.cursor = cursor;
+            state.drop_point();
+            state.record_undo(before);
+            states.push(state.undo_snapshot());
+        }
+
+        // moving the cursor alone is not recorded
+        let before = state.undo_marker();
+        state.cursor = Point { x: 100, y: 100 };
+        state.record_undo(before);
+        assert_eq!(state.undo_stack.len(), 3);
+        state.cursor = Point { x: 200, y: 200 };
+
+        state.undo();
+        assert_eq!(state.undo_snapshot(), states[2]);
+        state.undo();
+        assert_eq!(state.undo_snapshot(), states[1]);
+        state.redo();
+        assert_eq!(state.undo_snapshot(), states[2]);
+        state.redo();
+        assert_eq!(state.undo_snapshot(), states[3]);
+
+        // a new action after an undo forgets what could have been redone
+        state.undo();
+        let before = state.undo_marker();
+        state.cursor = Point { x: 0, y: 200 };
+        state.drop_point();
+        state.record_undo(before);
+        assert_eq!(state.redo_stack.len(), 0);
+        state.undo();
+        assert_eq!(state.undo_snapshot(), states[2]);
+    }
+}
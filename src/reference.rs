@@ -0,0 +1,172 @@
+use std::io;
+use std::path::Path;
+
+use sxd_document::dom::{ChildOfElement, ChildOfRoot, Element};
+
+use crate::{ClosedPath, Point};
+use crate::xml::{tokenize_path_data, PathToken};
+
+
+// shapes to trace over, never exported; only paths (M/L/H/V/Q/C/Z, also relative) and rects are
+// understood, without transformations or view boxes
+pub(crate) fn load_reference(path: &Path) -> io::Result<Vec<ClosedPath>> {
+    let text = std::fs::read_to_string(path)?;
+    let package = sxd_document::parser::parse(&text)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))?;
+    let doc = package.as_document();
+
+    let mut paths = Vec::new();
+    for child in doc.root().children() {
+        if let ChildOfRoot::Element(elem) = child {
+            collect_shapes(elem, &mut paths);
+        }
+    }
+    Ok(paths)
+}
+
+
+fn collect_shapes(elem: Element, paths: &mut Vec<ClosedPath>) {
+    match elem.name().local_part() {
+        "path" => {
+            if let Some(path) = elem.attribute_value("d").and_then(parse_path_data) {
+                paths.push(path);
+            }
+        },
+        "rect" => {
+            let number = |name| elem.attribute_value(name).and_then(|v| v.trim().parse::<f64>().ok());
+            if let (Some(x), Some(y), Some(width), Some(height)) = (number("x"), number("y"), number("width"), number("height")) {
+                let (left, top) = (x.round() as i32, y.round() as i32);
+                let (right, bottom) = ((x + width).round() as i32, (y + height).round() as i32);
+                paths.push(ClosedPath {
+                    points: vec![
+                        Point { x: left, y: top },
+                        Point { x: right, y: top },
+                        Point { x: right, y: bottom },
+                        Point { x: left, y: bottom },
+                    ],
+                    ..ClosedPath::default()
+                });
+            }
+        },
+        _ => {},
+    }
+
+    for child in elem.children() {
+        if let ChildOfElement::Element(child_elem) = child {
+            collect_shapes(child_elem, paths);
+        }
+    }
+}
+
+
+fn parse_path_data(data: &str) -> Option<ClosedPath> {
+    let tokens = tokenize_path_data(data);
+    let mut path = ClosedPath {
+        closed: false,
+        ..ClosedPath::default()
+    };
+    let mut current = (0.0, 0.0);
+    let mut subpath_start = (0.0, 0.0);
+    let mut command = None;
+    let mut i = 0;
+    let round = |(x, y): (f64, f64)| Point { x: x.round() as i32, y: y.round() as i32 };
+
+    while i < tokens.len() {
+        let c = match tokens[i] {
+            PathToken::Command(c) => {
+                i += 1;
+                if c == 'z' || c == 'Z' {
+                    path.closed = true;
+                    current = subpath_start;
+                    command = None;
+                    continue;
+                }
+                command = Some(c);
+                c
+            },
+            PathToken::Number(_) => match command {
+                Some(c) => c,
+                None => {
+                    // numbers without a command
+                    i += 1;
+                    continue;
+                },
+            },
+        };
+
+        let argument_count = match c.to_ascii_uppercase() {
+            'M' | 'L' => 2,
+            'H' | 'V' => 1,
+            'Q' => 4,
+            'C' => 6,
+            _ => {
+                // unsupported; skip its arguments
+                while let Some(PathToken::Number(_)) = tokens.get(i) {
+                    i += 1;
+                }
+                continue;
+            },
+        };
+        let arguments: Vec<f64> = tokens[i..].iter()
+            .take(argument_count)
+            .map_while(|token| match token {
+                PathToken::Number(number) => Some(*number),
+                PathToken::Command(_) => None,
+            })
+            .collect();
+        if arguments.len() < argument_count {
+            // incomplete; skip to the next command
+            i += arguments.len();
+            command = None;
+            continue;
+        }
+        i += argument_count;
+
+        let relative = c.is_ascii_lowercase();
+        let absolute = |x: f64, y: f64| if relative { (current.0 + x, current.1 + y) } else { (x, y) };
+        match c.to_ascii_uppercase() {
+            'M' => {
+                if path.points.len() > 0 {
+                    path.subpath_starts.push(path.points.len());
+                }
+                current = absolute(arguments[0], arguments[1]);
+                subpath_start = current;
+                path.points.push(round(current));
+                // further coordinate pairs are lines
+                command = Some(if relative { 'l' } else { 'L' });
+            },
+            'L' => {
+                current = absolute(arguments[0], arguments[1]);
+                path.points.push(round(current));
+            },
+            'H' => {
+                current.0 = if relative { current.0 + arguments[0] } else { arguments[0] };
+                path.points.push(round(current));
+            },
+            'V' => {
+                current.1 = if relative { current.1 + arguments[0] } else { arguments[0] };
+                path.points.push(round(current));
+            },
+            'Q' | 'C' => {
+                // all but the last coordinate pair are control points
+                let pairs: Vec<(f64, f64)> = arguments.chunks(2)
+                    .map(|xy| absolute(xy[0], xy[1]))
+                    .collect();
+                for (pair_index, pair) in pairs.iter().enumerate() {
+                    if pair_index < pairs.len() - 1 {
+                        path.control_points.push(path.points.len());
+                    }
+                    path.points.push(round(*pair));
+                }
+                current = pairs[pairs.len() - 1];
+            },
+            _ => unreachable!("unsupported command not skipped"),
+        }
+    }
+
+    if path.points.len() > 0 {
+        Some(path)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,121 @@
+use std::io;
+
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::WIN32_ERROR;
+use windows::Win32::System::Registry::{
+    HKEY, HKEY_CURRENT_USER, KEY_WRITE, RegCloseKey, RegCreateKeyExW, RegDeleteKeyValueW,
+    RegDeleteTreeW, REG_OPTION_NON_VOLATILE, REG_SZ, RegSetValueExW,
+};
+use windows::Win32::UI::Shell::{SHChangeNotify, SHCNE_ASSOCCHANGED, SHCNF_IDLIST};
+
+
+const PROG_ID: &str = "PixelPath.Project";
+
+// below HKEY_CURRENT_USER
+const CLASSES_KEY: &str = r"Software\Classes";
+
+
+// SVG files stay associated with whatever opened them before
+pub(crate) fn register_file_types() -> io::Result<()> {
+    let executable = std::env::current_exe()?;
+    let command = format!("\"{}\" \"%1\"", executable.display());
+
+    let prog_id_key = format!(r"{}\{}", CLASSES_KEY, PROG_ID);
+    set_string(&prog_id_key, None, "PixelPath Project")?;
+    set_string(&format!(r"{}\shell\open\command", prog_id_key), None, &command)?;
+    set_string(&format!(r"{}\.pixelpath", CLASSES_KEY), None, PROG_ID)?;
+    set_string(&format!(r"{}\.svg\OpenWithProgids", CLASSES_KEY), Some(PROG_ID), "")?;
+
+    notify_shell();
+    Ok(())
+}
+
+
+pub(crate) fn unregister_file_types() -> io::Result<()> {
+    delete_tree(&format!(r"{}\{}", CLASSES_KEY, PROG_ID))?;
+    delete_tree(&format!(r"{}\.pixelpath", CLASSES_KEY))?;
+    delete_value(&format!(r"{}\.svg\OpenWithProgids", CLASSES_KEY), PROG_ID)?;
+
+    notify_shell();
+    Ok(())
+}
+
+
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16()
+        .chain(std::iter::once(0x0000))
+        .collect()
+}
+
+
+// keeps the Windows error code so that missing keys and values are recognizable
+fn check(result: windows::core::Result<()>) -> io::Result<()> {
+    result.map_err(|e| match WIN32_ERROR::from_error(&e) {
+        Some(error) => io::Error::from_raw_os_error(error.0 as i32),
+        None => io::Error::other(e),
+    })
+}
+
+
+// the default value if `name` is `None`
+fn set_string(subkey: &str, name: Option<&str>, value: &str) -> io::Result<()> {
+    let subkey_wide = wide(subkey);
+    let mut key = HKEY::default();
+    check(unsafe {
+        RegCreateKeyExW(
+            HKEY_CURRENT_USER,
+            PCWSTR(subkey_wide.as_ptr()),
+            0,
+            PCWSTR::null(),
+            REG_OPTION_NON_VOLATILE,
+            KEY_WRITE,
+            None,
+            &mut key,
+            None,
+        )
+    })?;
+
+    let name_wide = name.map(wide);
+    let name_pointer = name_wide.as_ref()
+        .map_or(PCWSTR::null(), |name_wide| PCWSTR(name_wide.as_ptr()));
+    // the data includes the terminating NUL
+    let value_bytes: Vec<u8> = wide(value).into_iter()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+    let result = check(unsafe { RegSetValueExW(key, name_pointer, 0, REG_SZ, Some(&value_bytes)) });
+
+    // close the key whether or not setting the value worked
+    let _ = unsafe { RegCloseKey(key) };
+    result
+}
+
+
+// a missing key is no error
+fn delete_tree(subkey: &str) -> io::Result<()> {
+    let subkey_wide = wide(subkey);
+    ignore_not_found(check(unsafe { RegDeleteTreeW(HKEY_CURRENT_USER, PCWSTR(subkey_wide.as_ptr())) }))
+}
+
+
+// a missing value is no error
+fn delete_value(subkey: &str, name: &str) -> io::Result<()> {
+    let subkey_wide = wide(subkey);
+    let name_wide = wide(name);
+    ignore_not_found(check(unsafe {
+        RegDeleteKeyValueW(HKEY_CURRENT_USER, PCWSTR(subkey_wide.as_ptr()), PCWSTR(name_wide.as_ptr()))
+    }))
+}
+
+
+fn ignore_not_found(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+
+// makes Explorer pick up the changed associations right away
+fn notify_shell() {
+    unsafe { SHChangeNotify(SHCNE_ASSOCCHANGED, SHCNF_IDLIST, None, None) };
+}
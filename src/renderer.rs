@@ -0,0 +1,236 @@
+use once_cell::sync::Lazy;
+use windows::Win32::Foundation::{COLORREF, POINT};
+use windows::Win32::Graphics::Gdi::{ALTERNATE, HDC, SetPolyFillMode, WINDING};
+use windows::Win32::Graphics::GdiPlus::{
+    FillModeAlternate, FillModeWinding, GdipAddPathBezier, GdipAddPathLine, GdipClosePathFigure,
+    GdipCreateFromHDC, GdipCreatePath, GdipCreatePen1, GdipCreateSolidFill, GdipDeleteBrush,
+    GdipDeleteGraphics, GdipDeletePath, GdipDeletePen, GdipDrawPath, GdipFillPath, GdipResetPath,
+    GdipSetPixelOffsetMode, GdipSetSmoothingMode, GdipStartPathFigure, GdiplusStartup,
+    GdiplusStartupInput, GpBrush, GpGraphics, GpPath, GpPen, GpSolidFill, PixelOffsetModeHalf,
+    SmoothingModeAntiAlias, Status, UnitPixel,
+};
+
+use crate::{path_brush, path_pen};
+use crate::gdi_primitives::{
+    begin_path, close_figure, end_path, fill_path, fill_path_translucent, line_to, move_to,
+    poly_bezier_to, record_failure_message, select_object, stroke_path,
+};
+use crate::xml::FillRule;
+
+
+// GDI+ is kept running until the process exits
+static GDIPLUS_TOKEN: Lazy<usize> = Lazy::new(|| {
+    let input = GdiplusStartupInput {
+        GdiplusVersion: 1,
+        ..GdiplusStartupInput::default()
+    };
+    let mut token = 0;
+    check(unsafe { GdiplusStartup(&mut token, &input, std::ptr::null_mut()) }, "start up GDI+");
+    token
+});
+
+
+// receives outlines in device coordinates; each path is begun, built from figures and then
+// either filled or stroked, which also discards it
+pub(crate) trait PathRenderer {
+    fn begin_path(&mut self);
+    fn move_to(&mut self, x: i32, y: i32);
+    fn line_to(&mut self, x: i32, y: i32);
+
+    // three points per curve: two control points, then the end
+    fn poly_bezier_to(&mut self, points: &[POINT]);
+
+    fn close_figure(&mut self);
+    fn fill(&mut self, color: COLORREF);
+    fn fill_translucent(&mut self, color: COLORREF, opacity: u8);
+
+    fn stroke(&mut self, color: COLORREF);
+}
+
+
+pub(crate) struct GdiRenderer {
+    hdc: HDC,
+}
+impl GdiRenderer {
+    pub fn new(hdc: HDC, fill_rule: FillRule) -> Self {
+        let fill_mode = match fill_rule {
+            FillRule::EvenOdd => ALTERNATE,
+            FillRule::NonZero => WINDING,
+        };
+        unsafe { SetPolyFillMode(hdc, fill_mode) };
+        Self { hdc }
+    }
+}
+impl PathRenderer for GdiRenderer {
+    fn begin_path(&mut self) {
+        begin_path(self.hdc);
+    }
+
+    fn move_to(&mut self, x: i32, y: i32) {
+        move_to(self.hdc, x, y);
+    }
+
+    fn line_to(&mut self, x: i32, y: i32) {
+        line_to(self.hdc, x, y);
+    }
+
+    fn poly_bezier_to(&mut self, points: &[POINT]) {
+        poly_bezier_to(self.hdc, points);
+    }
+
+    fn close_figure(&mut self) {
+        close_figure(self.hdc);
+    }
+
+    fn fill(&mut self, color: COLORREF) {
+        end_path(self.hdc);
+        select_object(self.hdc, path_brush(color.0), "path brush");
+        fill_path(self.hdc);
+    }
+
+    fn fill_translucent(&mut self, color: COLORREF, opacity: u8) {
+        end_path(self.hdc);
+        fill_path_translucent(self.hdc, path_brush(color.0), opacity);
+    }
+
+    fn stroke(&mut self, color: COLORREF) {
+        end_path(self.hdc);
+        select_object(self.hdc, path_pen(color.0), "path pen");
+        stroke_path(self.hdc);
+    }
+}
+
+
+// like the GDI path functions, failing GDI+ calls are recorded (see take_failure) instead of
+// panicking while painting
+pub(crate) struct AntialiasedRenderer {
+    graphics: *mut GpGraphics,
+    path: *mut GpPath,
+    stroke_width: f32,
+    current: (f32, f32),
+}
+impl AntialiasedRenderer {
+    pub fn new(hdc: HDC, fill_rule: FillRule, stroke_width: u32) -> Self {
+        Lazy::force(&GDIPLUS_TOKEN);
+
+        let mut graphics = std::ptr::null_mut();
+        check(unsafe { GdipCreateFromHDC(hdc, &mut graphics) }, "create GDI+ graphics");
+        check(unsafe { GdipSetSmoothingMode(graphics, SmoothingModeAntiAlias) }, "enable antialiasing");
+        // GDI considers the center of a pixel to lie halfway between integer coordinates;
+        // match it so that switching renderers doesn't shift the drawing
+        check(unsafe { GdipSetPixelOffsetMode(graphics, PixelOffsetModeHalf) }, "set pixel offset mode");
+
+        let fill_mode = match fill_rule {
+            FillRule::EvenOdd => FillModeAlternate,
+            FillRule::NonZero => FillModeWinding,
+        };
+        let mut path = std::ptr::null_mut();
+        check(unsafe { GdipCreatePath(fill_mode, &mut path) }, "create GDI+ path");
+
+        Self {
+            graphics,
+            path,
+            stroke_width: stroke_width as f32,
+            current: (0.0, 0.0),
+        }
+    }
+}
+impl PathRenderer for AntialiasedRenderer {
+    fn begin_path(&mut self) {
+        check(unsafe { GdipResetPath(self.path) }, "reset path");
+    }
+
+    fn move_to(&mut self, x: i32, y: i32) {
+        check(unsafe { GdipStartPathFigure(self.path) }, "start figure");
+        self.current = (x as f32, y as f32);
+    }
+
+    fn line_to(&mut self, x: i32, y: i32) {
+        let (start_x, start_y) = self.current;
+        let (end_x, end_y) = (x as f32, y as f32);
+        check(unsafe { GdipAddPathLine(self.path, start_x, start_y, end_x, end_y) }, "add line");
+        self.current = (end_x, end_y);
+    }
+
+    fn poly_bezier_to(&mut self, points: &[POINT]) {
+        for curve in points.chunks_exact(3) {
+            let (start_x, start_y) = self.current;
+            let [control1, control2, end] = [curve[0], curve[1], curve[2]]
+                .map(|point| (point.x as f32, point.y as f32));
+            check(
+                unsafe {
+                    GdipAddPathBezier(
+                        self.path,
+                        start_x, start_y,
+                        control1.0, control1.1,
+                        control2.0, control2.1,
+                        end.0, end.1,
+                    )
+                },
+                "add Bézier curve",
+            );
+            self.current = end;
+        }
+    }
+
+    fn close_figure(&mut self) {
+        check(unsafe { GdipClosePathFigure(self.path) }, "close figure");
+    }
+
+    fn fill(&mut self, color: COLORREF) {
+        self.fill_translucent(color, 0xFF);
+    }
+
+    fn fill_translucent(&mut self, color: COLORREF, opacity: u8) {
+        let mut brush: *mut GpSolidFill = std::ptr::null_mut();
+        if check(unsafe { GdipCreateSolidFill(argb(color, opacity), &mut brush) }, "create GDI+ brush") {
+            check(unsafe { GdipFillPath(self.graphics, brush as *mut GpBrush, self.path) }, "fill path");
+            unsafe { GdipDeleteBrush(brush as *mut GpBrush) };
+        }
+        self.begin_path();
+    }
+
+    fn stroke(&mut self, color: COLORREF) {
+        let mut pen: *mut GpPen = std::ptr::null_mut();
+        let created = check(
+            unsafe { GdipCreatePen1(argb(color, 0xFF), self.stroke_width, UnitPixel, &mut pen) },
+            "create GDI+ pen",
+        );
+        if created {
+            check(unsafe { GdipDrawPath(self.graphics, pen, self.path) }, "stroke path");
+            unsafe { GdipDeletePen(pen) };
+        }
+        self.begin_path();
+    }
+}
+impl Drop for AntialiasedRenderer {
+    fn drop(&mut self) {
+        // either may have failed to be created
+        if !self.path.is_null() {
+            unsafe { GdipDeletePath(self.path) };
+        }
+        if !self.graphics.is_null() {
+            unsafe { GdipDeleteGraphics(self.graphics) };
+        }
+    }
+}
+
+
+// returns whether the call succeeded
+fn check(status: Status, action: &str) -> bool {
+    // Status(0) is "Ok"
+    if status.0 != 0 {
+        record_failure_message(format!("failed to {}: GDI+ status {}", action, status.0));
+        return false;
+    }
+    true
+}
+
+
+// 0x00BBGGRR and opacity to 0xAARRGGBB
+fn argb(color: COLORREF, opacity: u8) -> u32 {
+    let red = color.0 & 0xFF;
+    let green = (color.0 >> 8) & 0xFF;
+    let blue = (color.0 >> 16) & 0xFF;
+    (u32::from(opacity) << 24) | (red << 16) | (green << 8) | blue
+}
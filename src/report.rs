@@ -0,0 +1,31 @@
+use std::fmt::Write as _;
+
+use crate::ClosedPath;
+
+
+// as JSON, in drawing coordinates
+pub(crate) fn assemble_report(paths: &[ClosedPath]) -> String {
+    let mut ret = String::new();
+    writeln!(ret, "{{").unwrap();
+    writeln!(ret, "  \"paths\": [").unwrap();
+    for (i, path) in paths.iter().enumerate() {
+        write!(ret, "    {{").unwrap();
+        write!(ret, "\"index\": {}", i).unwrap();
+        write!(ret, ", \"vertices\": {}", path.points.len()).unwrap();
+        write!(ret, ", \"perimeter\": {}", path.perimeter()).unwrap();
+        write!(ret, ", \"area\": {}", path.area()).unwrap();
+        match path.bounding_box() {
+            Some((min, max)) => write!(
+                ret,
+                ", \"bounding_box\": {{\"min_x\": {}, \"min_y\": {}, \"max_x\": {}, \"max_y\": {}}}",
+                min.x, min.y, max.x, max.y,
+            ).unwrap(),
+            None => write!(ret, ", \"bounding_box\": null").unwrap(),
+        }
+        let separator = if i + 1 < paths.len() { "," } else { "" };
+        writeln!(ret, "}}{}", separator).unwrap();
+    }
+    writeln!(ret, "  ]").unwrap();
+    writeln!(ret, "}}").unwrap();
+    ret
+}
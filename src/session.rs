@@ -0,0 +1,467 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{
+    Acceleration, ApplicationState, CanvasConfig, ClosedPath, FrameSettings, GRID_ORIGIN_SUBDIVISIONS, GridLimit,
+    MAX_GRID_FACTOR, MAX_GRID_LIMIT, MIN_GRID_FACTOR, PathLimits, Point,
+};
+use crate::crosshair::{CrosshairColors, CrosshairLength};
+use crate::snapping::{SnapDistance, SnapMode};
+use crate::transform::MirrorAxis;
+use crate::view::{ResizeMode, Zoom};
+use crate::xml::{parse_svg, FillRule, MAX_PRECISION, SvgOptions, SvgStyle};
+
+
+const SESSION_MAGIC: &str = "pixelpath-session 1";
+const PROJECT_MAGIC: &str = "pixelpath-project 1";
+
+
+fn session_path() -> Option<PathBuf> {
+    let app_data = std::env::var_os("APPDATA")?;
+    let mut path = PathBuf::from(app_data);
+    path.push("PixelPath");
+    path.push("session.txt");
+    Some(path)
+}
+
+
+fn flag(value: bool) -> u8 {
+    if value { 1 } else { 0 }
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    }
+}
+
+fn parse_numbers<T: std::str::FromStr>(value: &str) -> Option<Vec<T>> {
+    value.split(' ')
+        .filter(|piece| piece.len() > 0)
+        .map(|piece| piece.parse().ok())
+        .collect()
+}
+
+fn parse_point(value: &str) -> Option<Point> {
+    match parse_numbers(value)?.as_slice() {
+        &[x, y] => Some(Point { x, y }),
+        _ => None,
+    }
+}
+
+fn parse_canvas(value: &str) -> Option<CanvasConfig> {
+    match parse_numbers(value)?.as_slice() {
+        &[left_offset, top_offset, horizontal_factor, vertical_factor] => Some(CanvasConfig {
+            left_offset,
+            top_offset,
+            // the same cell sizes that Ctrl+H and Ctrl+V allow
+            horizontal_factor: horizontal_factor.clamp(MIN_GRID_FACTOR, MAX_GRID_FACTOR),
+            vertical_factor: vertical_factor.clamp(MIN_GRID_FACTOR, MAX_GRID_FACTOR),
+        }),
+        _ => None,
+    }
+}
+
+
+// the document and the editor state worth resuming
+fn serialize_session(state: &ApplicationState) -> String {
+    let mut ret = String::new();
+    writeln!(ret, "{}", SESSION_MAGIC).unwrap();
+    if let Some(current_file) = &state.current_file {
+        writeln!(ret, "file {}", current_file.display()).unwrap();
+    }
+    writeln!(ret, "cursor {} {}", state.cursor.x, state.cursor.y).unwrap();
+    writeln!(ret, "zoom {} {}", state.zoom.numerator, state.zoom.denominator).unwrap();
+    writeln!(ret, "viewport {} {}", state.viewport.x, state.viewport.y).unwrap();
+    writeln!(ret, "resize_mode {}", state.resize_mode.name()).unwrap();
+    writeln!(ret, "fill_canvas {}", flag(state.fill_canvas)).unwrap();
+    writeln!(ret, "solid_grid {}", flag(state.solid_grid)).unwrap();
+    writeln!(ret, "antialias {}", flag(state.antialias)).unwrap();
+    writeln!(ret, "outline_preview {}", flag(state.outline_preview)).unwrap();
+    writeln!(ret, "dark_mode {}", flag(state.dark_mode)).unwrap();
+    writeln!(ret, "cell_paint {}", flag(state.cell_paint)).unwrap();
+    writeln!(ret, "cell_color {}", state.cell_color).unwrap();
+    writeln!(ret, "snap {}", state.snap_mode.name()).unwrap();
+    writeln!(ret, "snap_distance {}", state.snap_distance.0).unwrap();
+    writeln!(ret, "crosshair_length {}", state.crosshair_length.0).unwrap();
+    writeln!(ret, "crosshair_colors {}", state.crosshair_colors.name()).unwrap();
+    writeln!(ret, "accelerate_cursor {}", flag(state.accelerate_cursor)).unwrap();
+    writeln!(
+        ret, "acceleration {} {} {}",
+        state.acceleration.window.as_millis(), state.acceleration.presses_per_step, state.acceleration.max_steps,
+    ).unwrap();
+    writeln!(ret, "clamp_to_grid {}", flag(state.clamp_to_grid)).unwrap();
+    writeln!(ret, "constrain_angles {}", flag(state.constrain_angles)).unwrap();
+    writeln!(ret, "grid_limit {} {}", state.grid_limit.0.x, state.grid_limit.0.y).unwrap();
+    writeln!(
+        ret, "frame_settings {} {} {}",
+        state.frame_settings.aspect.x, state.frame_settings.aspect.y, state.frame_settings.output_width,
+    ).unwrap();
+    write!(ret, "path_limits {}", state.path_limits.warning).unwrap();
+    match state.path_limits.hard_limit {
+        Some(hard_limit) => writeln!(ret, " {}", hard_limit).unwrap(),
+        None => writeln!(ret, " none").unwrap(),
+    }
+    writeln!(ret, "mirror_axis {}", state.mirror_axis.name()).unwrap();
+    writeln!(ret, "symmetry {}", flag(state.symmetry)).unwrap();
+    writeln!(ret, "edit_mode {}", flag(state.edit_mode)).unwrap();
+    for (path_index, point_index) in &state.selected_vertices {
+        writeln!(ret, "selected {} {}", path_index, point_index).unwrap();
+    }
+    if let Some((path_index, point_index)) = state.vertex_cursor {
+        writeln!(ret, "vertex_cursor {} {}", path_index, point_index).unwrap();
+    }
+    write_document(&mut ret, state);
+    ret
+}
+
+
+// the drawing only, without editor state
+fn serialize_project(state: &ApplicationState) -> String {
+    let mut ret = String::new();
+    writeln!(ret, "{}", PROJECT_MAGIC).unwrap();
+    write_document(&mut ret, state);
+    ret
+}
+
+
+fn write_document(ret: &mut String, state: &ApplicationState) {
+    writeln!(ret, "grid {} {}", state.grid_count.x, state.grid_count.y).unwrap();
+    writeln!(
+        ret, "canvas {} {} {} {}",
+        state.canvas.left_offset, state.canvas.top_offset,
+        state.canvas.horizontal_factor, state.canvas.vertical_factor,
+    ).unwrap();
+    writeln!(ret, "grid_origin {} {}", state.grid_origin.x, state.grid_origin.y).unwrap();
+    writeln!(ret, "crisp_edges {}", flag(state.svg_options.crisp_edges)).unwrap();
+    writeln!(ret, "svg_style {}", state.svg_options.style.name()).unwrap();
+    writeln!(ret, "fill_rule {}", state.svg_options.fill_rule.name()).unwrap();
+    writeln!(ret, "svg_precision {}", state.svg_options.precision).unwrap();
+    writeln!(ret, "svg_cell_units {}", flag(state.svg_options.cell_units)).unwrap();
+    writeln!(ret, "svg_path_ids {}", flag(state.svg_options.path_ids)).unwrap();
+    match state.svg_options.background {
+        Some(color) => writeln!(ret, "svg_background {}", color).unwrap(),
+        None => writeln!(ret, "svg_background none").unwrap(),
+    }
+    for path in &state.paths {
+        write!(ret, "path {}", flag(path.cell)).unwrap();
+        for point in &path.points {
+            write!(ret, " {} {}", point.x, point.y).unwrap();
+        }
+        writeln!(ret).unwrap();
+        if path.smooth {
+            // applies to the preceding path
+            writeln!(ret, "smooth {}", path.tension_percent).unwrap();
+        }
+        if !path.closed {
+            writeln!(ret, "closed {}", flag(path.closed)).unwrap();
+        }
+        if path.color != ClosedPath::default().color {
+            writeln!(ret, "color {}", path.color).unwrap();
+        }
+        if path.subpath_starts.len() > 0 {
+            write!(ret, "subpaths").unwrap();
+            for start in &path.subpath_starts {
+                write!(ret, " {}", start).unwrap();
+            }
+            writeln!(ret).unwrap();
+        }
+        if path.control_points.len() > 0 {
+            write!(ret, "controls").unwrap();
+            for index in &path.control_points {
+                write!(ret, " {}", index).unwrap();
+            }
+            writeln!(ret).unwrap();
+        }
+        if let Some(label) = &path.label {
+            // the rest of the line
+            writeln!(ret, "label {}", label.replace(['\r', '\n'], " ")).unwrap();
+        }
+    }
+}
+
+
+// unknown or malformed lines are skipped
+fn deserialize_state(text: &str, magic: &str) -> Option<ApplicationState> {
+    let mut lines = text.lines();
+    if lines.next() != Some(magic) {
+        return None;
+    }
+
+    let mut state = ApplicationState::default();
+    for line in lines {
+        let (key, value) = match line.split_once(' ') {
+            Some(kv) => kv,
+            None => continue,
+        };
+        match key {
+            "file" => state.current_file = Some(PathBuf::from(value)),
+            "cursor" => if let Some(p) = parse_point(value) { state.cursor = p },
+            "grid" => if let Some(p) = parse_point(value) { state.grid_count = p },
+            "canvas" => if let Some(c) = parse_canvas(value) { state.canvas = c },
+            "zoom" => if let Some(&[numerator, denominator]) = parse_numbers(value).as_deref() {
+                if numerator > 0 && denominator > 0 {
+                    state.zoom = Zoom { numerator, denominator };
+                }
+            },
+            "viewport" => if let Some(p) = parse_point(value) { state.viewport = p },
+            "fill_canvas" => if let Some(f) = parse_flag(value) { state.fill_canvas = f },
+            "solid_grid" => if let Some(f) = parse_flag(value) { state.solid_grid = f },
+            "antialias" => if let Some(f) = parse_flag(value) { state.antialias = f },
+            "outline_preview" => if let Some(f) = parse_flag(value) { state.outline_preview = f },
+            "dark_mode" => if let Some(f) = parse_flag(value) { state.dark_mode = f },
+            "crisp_edges" => if let Some(f) = parse_flag(value) { state.svg_options.crisp_edges = f },
+            "svg_style" => if let Some(s) = SvgStyle::from_name(value) { state.svg_options.style = s },
+            "fill_rule" => if let Some(r) = FillRule::from_name(value) { state.svg_options.fill_rule = r },
+            "svg_precision" => if let Ok(p) = value.parse::<usize>() { state.svg_options.precision = p.min(MAX_PRECISION) },
+            "svg_cell_units" => if let Some(f) = parse_flag(value) { state.svg_options.cell_units = f },
+            "svg_path_ids" => if let Some(f) = parse_flag(value) { state.svg_options.path_ids = f },
+            "svg_background" => state.svg_options.background = value.parse().ok(),
+            "cell_paint" => if let Some(f) = parse_flag(value) { state.cell_paint = f },
+            "cell_color" => if let Ok(c) = value.parse() { state.cell_color = c },
+            "snap" => if let Some(m) = SnapMode::from_name(value) { state.snap_mode = m },
+            "snap_distance" => if let Ok(d) = value.parse() {
+                if d >= 0 {
+                    state.snap_distance = SnapDistance(d);
+                }
+            },
+            "crosshair_length" => if let Ok(l) = value.parse() { state.crosshair_length = CrosshairLength::clamped(l) },
+            "crosshair_colors" => if let Some(c) = CrosshairColors::from_name(value) { state.crosshair_colors = c },
+            "resize_mode" => if let Some(m) = ResizeMode::from_name(value) { state.resize_mode = m },
+            "accelerate_cursor" => if let Some(f) = parse_flag(value) { state.accelerate_cursor = f },
+            "acceleration" => if let Some(&[window_ms, presses_per_step, max_steps]) = parse_numbers::<i32>(value).as_deref() {
+                let window = Duration::from_millis(window_ms.try_into().unwrap_or(0));
+                state.acceleration = Acceleration::clamped(window, presses_per_step, max_steps);
+            },
+            "clamp_to_grid" => if let Some(f) = parse_flag(value) { state.clamp_to_grid = f },
+            "constrain_angles" => if let Some(f) = parse_flag(value) { state.constrain_angles = f },
+            "grid_origin" => if let Some(p) = parse_point(value) {
+                state.grid_origin = Point {
+                    x: p.x.rem_euclid(GRID_ORIGIN_SUBDIVISIONS),
+                    y: p.y.rem_euclid(GRID_ORIGIN_SUBDIVISIONS),
+                };
+            },
+            "grid_limit" => if let Some(p) = parse_point(value) { state.grid_limit = GridLimit::clamped(p) },
+            "frame_settings" => if let Some(&[x, y, output_width]) = parse_numbers(value).as_deref() {
+                state.frame_settings = FrameSettings::clamped(Point { x, y }, output_width);
+            },
+            "path_limits" => if let Some((warning, hard_limit)) = value.split_once(' ') {
+                if let Ok(warning) = warning.parse() {
+                    state.path_limits = PathLimits {
+                        warning,
+                        hard_limit: hard_limit.parse().ok().filter(|&limit| limit > 0),
+                    };
+                }
+            },
+            "mirror_axis" => if let Some(a) = MirrorAxis::from_name(value) { state.mirror_axis = a },
+            "symmetry" => if let Some(f) = parse_flag(value) { state.symmetry = f },
+            "edit_mode" => if let Some(f) = parse_flag(value) { state.edit_mode = f },
+            "selected" => if let Some(&[path_index, point_index]) = parse_numbers(value).as_deref() {
+                state.selected_vertices.insert((path_index, point_index));
+            },
+            "vertex_cursor" => if let Some(&[path_index, point_index]) = parse_numbers(value).as_deref() {
+                state.vertex_cursor = Some((path_index, point_index));
+            },
+            "path" => {
+                let numbers: Vec<i32> = match parse_numbers(value) {
+                    Some(n) => n,
+                    None => continue,
+                };
+                if numbers.len() % 2 != 1 {
+                    continue;
+                }
+                let points = numbers[1..].chunks(2)
+                    .map(|xy| Point { x: xy[0], y: xy[1] })
+                    .collect();
+                state.paths.push(ClosedPath {
+                    points,
+                    cell: numbers[0] != 0,
+                    ..ClosedPath::default()
+                });
+            },
+            "smooth" => if let (Some(path), Ok(tension_percent)) = (state.paths.last_mut(), value.parse()) {
+                path.smooth = true;
+                path.tension_percent = tension_percent;
+            },
+            "closed" => if let (Some(path), Some(f)) = (state.paths.last_mut(), parse_flag(value)) {
+                path.closed = f;
+            },
+            "color" => if let (Some(path), Ok(color)) = (state.paths.last_mut(), value.parse()) {
+                path.color = color;
+            },
+            "subpaths" => if let (Some(path), Some(starts)) = (state.paths.last_mut(), parse_numbers(value)) {
+                path.subpath_starts = starts;
+            },
+            "controls" => if let (Some(path), Some(indexes)) = (state.paths.last_mut(), parse_numbers(value)) {
+                path.control_points = indexes;
+            },
+            "label" => if let Some(path) = state.paths.last_mut() {
+                path.label = Some(String::from(value));
+            },
+            _ => {},
+        }
+    }
+
+    // projects do not carry a limit of their own; the editor applies its own when opening them
+    let grid_limit = if magic == SESSION_MAGIC { state.grid_limit } else { GridLimit(MAX_GRID_LIMIT) };
+    state.grid_count = grid_limit.apply(state.grid_count);
+    Some(state)
+}
+
+
+pub(crate) fn save_session(state: &ApplicationState) -> std::io::Result<()> {
+    let path = match session_path() {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serialize_session(state))
+}
+
+
+pub(crate) fn load_session() -> Option<ApplicationState> {
+    let path = session_path()?;
+    let text = std::fs::read_to_string(&path).ok()?;
+    deserialize_state(&text, SESSION_MAGIC)
+}
+
+
+// unlike SVG, a project can be opened again for editing
+pub(crate) fn save_project(path: &Path, state: &ApplicationState) -> std::io::Result<()> {
+    std::fs::write(path, serialize_project(state))
+}
+
+
+// only the drawing is restored
+pub(crate) fn load_project(path: &Path) -> std::io::Result<ApplicationState> {
+    let text = std::fs::read_to_string(path)?;
+    deserialize_state(&text, PROJECT_MAGIC)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "not a PixelPath project"))
+}
+
+
+// the cell size gives the size of the grid; only the drawing is restored
+pub(crate) fn load_svg(path: &Path, canvas: CanvasConfig, svg_options: SvgOptions) -> std::io::Result<ApplicationState> {
+    let text = std::fs::read_to_string(path)?;
+    let (grid_count, paths) = parse_svg(&text, canvas)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    Ok(ApplicationState {
+        paths,
+        grid_count,
+        canvas,
+        svg_options,
+        ..ApplicationState::default()
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_restores_the_drawing() {
+        let state = ApplicationState {
+            grid_count: Point { x: 8, y: 6 },
+            paths: vec![
+                ClosedPath {
+                    points: ClosedPath::from_coords(&[
+                        (0, 0), (400, 0), (400, 400), (0, 400), (100, 100), (100, 300), (300, 300),
+                    ]).points,
+                    subpath_starts: vec![4],
+                    color: 0x00C000,
+                    label: Some(String::from("donut")),
+                    ..ClosedPath::default()
+                },
+                ClosedPath {
+                    points: ClosedPath::from_coords(&[(0, 0), (50, 70), (100, 0)]).points,
+                    smooth: true,
+                    tension_percent: 50,
+                    closed: false,
+                    control_points: vec![1],
+                    ..ClosedPath::default()
+                },
+            ],
+            is_drawing: true,
+            ..ApplicationState::default()
+        };
+
+        let project = deserialize_state(&serialize_project(&state), PROJECT_MAGIC).unwrap();
+        assert_eq!(project.paths, state.paths);
+        assert_eq!(project.grid_count, state.grid_count);
+        assert!(!project.is_drawing);
+
+        // a project is not a session and vice versa
+        assert!(deserialize_state(&serialize_project(&state), SESSION_MAGIC).is_none());
+    }
+
+    #[test]
+    fn loaded_grid_is_clamped_to_the_limit() {
+        let state = deserialize_state("pixelpath-session 1\ngrid 500 7\n", SESSION_MAGIC).unwrap();
+        assert_eq!(state.grid_count, Point { x: 100, y: 7 });
+
+        let state = deserialize_state("pixelpath-session 1\ngrid_limit 600 600\ngrid 500 7\n", SESSION_MAGIC).unwrap();
+        assert_eq!(state.grid_count, Point { x: 500, y: 7 });
+
+        let project = deserialize_state("pixelpath-project 1\ngrid 5000 -3\n", PROJECT_MAGIC).unwrap();
+        assert_eq!(project.grid_count, Point { x: 1000, y: 0 });
+    }
+
+    #[test]
+    fn loaded_cell_size_is_clamped() {
+        let state = deserialize_state("pixelpath-session 1\ncanvas 5 6 2147483647 0\n", SESSION_MAGIC).unwrap();
+        assert_eq!(state.canvas, CanvasConfig {
+            left_offset: 5,
+            top_offset: 6,
+            horizontal_factor: MAX_GRID_FACTOR,
+            vertical_factor: MIN_GRID_FACTOR,
+        });
+    }
+
+    #[test]
+    fn acceleration_is_restored() {
+        let state = ApplicationState {
+            acceleration: Acceleration::clamped(Duration::from_millis(300), 2, 4),
+            ..ApplicationState::default()
+        };
+        let restored = deserialize_state(&serialize_session(&state), SESSION_MAGIC).unwrap();
+        assert_eq!(restored.acceleration, state.acceleration);
+        assert_eq!(restored.acceleration.step_multiplier(5), 3);
+        assert_eq!(restored.acceleration.step_multiplier(50), 4);
+
+        let state = deserialize_state("pixelpath-session 1\nacceleration -5 0 1000\n", SESSION_MAGIC).unwrap();
+        assert_eq!(state.acceleration, Acceleration::clamped(Duration::from_millis(1), 1, 100));
+    }
+
+    #[test]
+    fn path_limits_are_restored() {
+        let state = ApplicationState {
+            path_limits: PathLimits { warning: 50, hard_limit: Some(200) },
+            ..ApplicationState::default()
+        };
+        let restored = deserialize_state(&serialize_session(&state), SESSION_MAGIC).unwrap();
+        assert_eq!(restored.path_limits, state.path_limits);
+
+        let state = deserialize_state("pixelpath-session 1\npath_limits 50 none\n", SESSION_MAGIC).unwrap();
+        assert_eq!(state.path_limits, PathLimits { warning: 50, hard_limit: None });
+    }
+
+    #[test]
+    fn frame_settings_are_restored() {
+        let state = ApplicationState {
+            frame_settings: FrameSettings::clamped(Point { x: 4, y: 3 }, 800),
+            ..ApplicationState::default()
+        };
+        let restored = deserialize_state(&serialize_session(&state), SESSION_MAGIC).unwrap();
+        assert_eq!(restored.frame_settings, state.frame_settings);
+        assert_eq!(restored.frame_settings.output_size(), Point { x: 800, y: 600 });
+
+        let state = deserialize_state("pixelpath-session 1\nframe_settings 0 9 100000\n", SESSION_MAGIC).unwrap();
+        assert_eq!(state.frame_settings, FrameSettings::clamped(Point { x: 1, y: 9 }, 16384));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{CanvasConfig, ClosedPath, Point};
+
+
+// in each direction
+const DEFAULT_SNAP_DISTANCE: i32 = 40;
+const SNAP_DISTANCE_STEP: i32 = 10;
+const MAX_SNAP_DISTANCE: i32 = 400;
+
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum SnapMode {
+    #[default]
+    Off,
+    Grid,
+    Vertex,
+    Edge,
+    All,
+}
+impl SnapMode {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Off => Self::Grid,
+            Self::Grid => Self::Vertex,
+            Self::Vertex => Self::Edge,
+            Self::Edge => Self::All,
+            Self::All => Self::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Grid => "grid",
+            Self::Vertex => "vertex",
+            Self::Edge => "edge",
+            Self::All => "all",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Off, Self::Grid, Self::Vertex, Self::Edge, Self::All]
+            .into_iter()
+            .find(|mode| mode.name() == name)
+    }
+}
+
+
+// in each direction, in drawing coordinates
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct SnapDistance(pub i32);
+impl SnapDistance {
+    pub fn grow(&mut self) {
+        self.0 = (self.0 + SNAP_DISTANCE_STEP).min(MAX_SNAP_DISTANCE);
+    }
+
+    // down to exactly matching positions
+    pub fn shrink(&mut self) {
+        self.0 = (self.0 - SNAP_DISTANCE_STEP).max(0);
+    }
+}
+impl Default for SnapDistance {
+    fn default() -> Self {
+        Self(DEFAULT_SNAP_DISTANCE)
+    }
+}
+
+
+fn distance_squared(a: Point, b: Point) -> i64 {
+    let dx = i64::from(a.x) - i64::from(b.x);
+    let dy = i64::from(a.y) - i64::from(b.y);
+    dx * dx + dy * dy
+}
+
+pub(crate) fn round_to_multiple(value: i32, factor: i32) -> i32 {
+    (value + factor / 2).div_euclid(factor) * factor
+}
+
+// stays within the grid if there is one
+fn grid_candidate(cursor: Point, grid_count: Point, canvas: CanvasConfig, grid_shift: Point) -> Point {
+    let mut candidate = Point {
+        x: round_to_multiple(cursor.x - grid_shift.x, canvas.horizontal_factor) + grid_shift.x,
+        y: round_to_multiple(cursor.y - grid_shift.y, canvas.vertical_factor) + grid_shift.y,
+    };
+    if grid_count.x > 0 && grid_count.y > 0 {
+        candidate.x = candidate.x.clamp(0, grid_count.x * canvas.horizontal_factor);
+        candidate.y = candidate.y.clamp(0, grid_count.y * canvas.vertical_factor);
+    }
+    candidate
+}
+
+fn vertex_candidates(paths: &[ClosedPath]) -> impl Iterator<Item = Point> + '_ {
+    paths.iter()
+        .flat_map(|path| path.points.iter().copied())
+}
+
+// subpaths only wrap around if the path is closed; edges ending in control points belong to
+// curves, whose midpoints lie elsewhere
+fn edge_candidates(paths: &[ClosedPath]) -> impl Iterator<Item = Point> + '_ {
+    paths.iter()
+        .flat_map(|path| path.subpath_ranges().into_iter().map(move |range| (path, range)))
+        .flat_map(|(path, range)| {
+            let edge_count = match (range.len(), path.closed) {
+                (0 | 1, _) => 0,
+                (count, true) => count,
+                (count, false) => count - 1,
+            };
+            (0..edge_count)
+                .map(move |i| {
+                    let start_index = range.start + i;
+                    let end_index = range.start + (i + 1) % range.len();
+                    (start_index, end_index)
+                })
+                .filter(move |(start_index, end_index)| {
+                    !path.control_points.contains(start_index) && !path.control_points.contains(end_index)
+                })
+                .map(move |(start_index, end_index)| {
+                    let start = path.points[start_index];
+                    let end = path.points[end_index];
+                    Point {
+                        x: (start.x + end.x) / 2,
+                        y: (start.y + end.y) / 2,
+                    }
+                })
+        })
+}
+
+
+fn nearest_within_reach<I: Iterator<Item = Point>>(
+    cursor: Point,
+    candidates: I,
+    distance: SnapDistance,
+) -> Option<Point> {
+    candidates
+        .filter(|c| (c.x - cursor.x).abs() <= distance.0 && (c.y - cursor.y).abs() <= distance.0)
+        .min_by_key(|c| distance_squared(*c, cursor))
+}
+
+
+pub(crate) fn snap(
+    mode: SnapMode,
+    distance: SnapDistance,
+    cursor: Point,
+    paths: &[ClosedPath],
+    grid_count: Point,
+    canvas: CanvasConfig,
+    grid_shift: Point,
+) -> Point {
+    match mode {
+        SnapMode::Off => cursor,
+        SnapMode::Grid => grid_candidate(cursor, grid_count, canvas, grid_shift),
+        SnapMode::Vertex => nearest_within_reach(cursor, vertex_candidates(paths), distance)
+            .unwrap_or(cursor),
+        SnapMode::Edge => nearest_within_reach(cursor, edge_candidates(paths), distance)
+            .unwrap_or(cursor),
+        SnapMode::All => {
+            // vertices and edges attract more strongly than the grid
+            let geometry = vertex_candidates(paths)
+                .chain(edge_candidates(paths));
+            nearest_within_reach(cursor, geometry, distance)
+                .unwrap_or_else(|| grid_candidate(cursor, grid_count, canvas, grid_shift))
+        },
+    }
+}
+
+
+// snaps the edge from `previous` to horizontal, vertical or diagonal; `step` is the lattice
+// spacing (the cell size when snapped to the grid, 1x1 otherwise), and diagonals advance by the
+// same number of steps in both directions so the result stays on the lattice
+pub(crate) fn constrain_angle(previous: Point, point: Point, step: Point) -> Point {
+    let dx = point.x - previous.x;
+    let dy = point.y - previous.y;
+    // the boundaries between the directions lie halfway, at 22.5° from each of them
+    let tan_half_step = std::f64::consts::SQRT_2 - 1.0;
+    let (abs_dx, abs_dy) = (f64::from(dx.abs()), f64::from(dy.abs()));
+    if abs_dy <= abs_dx * tan_half_step {
+        Point { x: point.x.max(0), y: previous.y }
+    } else if abs_dx <= abs_dy * tan_half_step {
+        Point { x: previous.x, y: point.y.max(0) }
+    } else {
+        let steps_x = abs_dx / f64::from(step.x);
+        let steps_y = abs_dy / f64::from(step.y);
+        let mut steps = ((steps_x + steps_y) / 2.0).round() as i32;
+        // stop at the edge of the canvas instead of leaving the diagonal
+        if dx < 0 {
+            steps = steps.min(previous.x.max(0) / step.x);
+        }
+        if dy < 0 {
+            steps = steps.min(previous.y.max(0) / step.y);
+        }
+        Point {
+            x: previous.x + steps * step.x * dx.signum(),
+            y: previous.y + steps * step.y * dy.signum(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIT: Point = Point { x: 1, y: 1 };
+
+    #[test]
+    fn open_paths_have_no_closing_edge() {
+        let open = ClosedPath {
+            points: vec![Point { x: 0, y: 0 }, Point { x: 200, y: 0 }, Point { x: 200, y: 200 }],
+            closed: false,
+            ..ClosedPath::default()
+        };
+        let midpoints: Vec<Point> = edge_candidates(std::slice::from_ref(&open)).collect();
+        assert_eq!(midpoints, vec![Point { x: 100, y: 0 }, Point { x: 200, y: 100 }]);
+    }
+
+    #[test]
+    fn grid_snapping_follows_the_grid_origin() {
+        let shift = Point { x: 30, y: 50 };
+        let snapped = grid_candidate(Point { x: 170, y: 240 }, Point { x: 4, y: 4 }, CanvasConfig::default(), shift);
+        assert_eq!(snapped, Point { x: 130, y: 250 });
+    }
+
+    #[test]
+    fn constrain_angle_to_horizontal() {
+        let constrained = constrain_angle(Point { x: 100, y: 100 }, Point { x: 300, y: 150 }, UNIT);
+        assert_eq!(constrained, Point { x: 300, y: 100 });
+    }
+
+    #[test]
+    fn constrain_angle_to_vertical() {
+        let constrained = constrain_angle(Point { x: 100, y: 100 }, Point { x: 130, y: 400 }, UNIT);
+        assert_eq!(constrained, Point { x: 100, y: 400 });
+    }
+
+    #[test]
+    fn constrain_angle_to_diagonal() {
+        let constrained = constrain_angle(Point { x: 100, y: 100 }, Point { x: 300, y: 280 }, UNIT);
+        assert_eq!(constrained, Point { x: 290, y: 290 });
+    }
+
+    #[test]
+    fn constrained_diagonal_stays_on_the_lattice() {
+        let cell = Point { x: 100, y: 50 };
+        let constrained = constrain_angle(Point { x: 100, y: 100 }, Point { x: 300, y: 200 }, cell);
+        assert_eq!(constrained, Point { x: 300, y: 200 });
+        let constrained = constrain_angle(Point { x: 100, y: 100 }, Point { x: 480, y: 270 }, cell);
+        assert_eq!(constrained, Point { x: 500, y: 300 });
+    }
+
+    #[test]
+    fn constrained_point_is_not_negative() {
+        let constrained = constrain_angle(Point { x: 50, y: 100 }, Point { x: -100, y: 110 }, UNIT);
+        assert_eq!(constrained, Point { x: 0, y: 100 });
+        let constrained = constrain_angle(Point { x: 100, y: 100 }, Point { x: -200, y: -180 }, UNIT);
+        assert_eq!(constrained, Point { x: 0, y: 0 });
+    }
+}
@@ -0,0 +1,142 @@
+use crate::Point;
+
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct CubicSegment {
+    pub control1: Point,
+    pub control2: Point,
+    pub end: Point,
+}
+
+
+// each piece starts wherever the previous one ended
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Segment {
+    Line(Point),
+
+    // control point, then end point
+    Quad(Point, Point),
+
+    // two control points, then end point
+    Cubic(Point, Point, Point),
+}
+impl Segment {
+    pub fn end(&self) -> Point {
+        match self {
+            Self::Line(end) => *end,
+            Self::Quad(_control, end) => *end,
+            Self::Cubic(_control1, _control2, end) => *end,
+        }
+    }
+
+    // cubic is all GDI can draw
+    pub fn to_cubic(self, start: Point) -> CubicSegment {
+        // a third of the way from each end point towards the quadratic control point
+        let two_thirds = |from: Point, towards: Point| Point {
+            x: from.x + (2 * (towards.x - from.x)) / 3,
+            y: from.y + (2 * (towards.y - from.y)) / 3,
+        };
+        match self {
+            Self::Line(end) => CubicSegment { control1: start, control2: end, end },
+            Self::Quad(control, end) => CubicSegment {
+                control1: two_thirds(start, control),
+                control2: two_thirds(end, control),
+                end,
+            },
+            Self::Cubic(control1, control2, end) => CubicSegment { control1, control2, end },
+        }
+    }
+}
+
+
+// a tension of 0% is Catmull-Rom, 100% straight edges; fewer than three points give no segments,
+// and the ends of an open spline leave as if the end vertex were doubled
+pub(crate) fn cardinal_spline(points: &[Point], tension_percent: i32, closed: bool) -> Vec<CubicSegment> {
+    let count = points.len();
+    if count < 3 {
+        return Vec::new();
+    }
+
+    // each control point lies a sixth of the tangent away from its vertex (Catmull-Rom),
+    // scaled down by the tension
+    let factor = f64::from(100 - tension_percent) / 600.0;
+    let offset = |from: Point, towards_minus: Point, towards_plus: Point, sign: f64| Point {
+        x: (f64::from(from.x) + sign * factor * f64::from(towards_plus.x - towards_minus.x)).round() as i32,
+        y: (f64::from(from.y) + sign * factor * f64::from(towards_plus.y - towards_minus.y)).round() as i32,
+    };
+
+    // an open spline has no segment from the last point back to the first
+    let segment_count = if closed { count } else { count - 1 };
+    let neighbor = |i: usize, delta: isize| -> Point {
+        let index = i as isize + delta;
+        if closed {
+            points[index.rem_euclid(count as isize) as usize]
+        } else {
+            points[index.clamp(0, count as isize - 1) as usize]
+        }
+    };
+
+    (0..segment_count)
+        .map(|i| {
+            let previous = neighbor(i, -1);
+            let start = points[i];
+            let end = neighbor(i, 1);
+            let next = neighbor(i, 2);
+            CubicSegment {
+                control1: offset(start, previous, end, 1.0),
+                control2: offset(end, start, next, -1.0),
+                end,
+            }
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClosedPath;
+
+    fn segment(control1: (i32, i32), control2: (i32, i32), end: (i32, i32)) -> CubicSegment {
+        let point = |(x, y)| Point { x, y };
+        CubicSegment { control1: point(control1), control2: point(control2), end: point(end) }
+    }
+
+    #[test]
+    fn closed_spline_leaves_each_vertex_parallel_to_its_neighbors() {
+        let square = ClosedPath::from_coords(&[(0, 0), (600, 0), (600, 600), (0, 600)]).points;
+        let spline = cardinal_spline(&square, 0, true);
+        assert_eq!(spline.len(), 4);
+        assert_eq!(spline[0], segment((100, -100), (500, -100), (600, 0)));
+        assert_eq!(spline[3], segment((-100, 500), (-100, 100), (0, 0)));
+    }
+
+    #[test]
+    fn open_spline_ends_towards_the_neighboring_vertex() {
+        let spline = cardinal_spline(&ClosedPath::from_coords(&[(0, 0), (300, 300), (600, 0)]).points, 0, false);
+        assert_eq!(spline, vec![
+            segment((50, 50), (200, 300), (300, 300)),
+            segment((400, 300), (550, 50), (600, 0)),
+        ]);
+    }
+
+    #[test]
+    fn full_tension_gives_straight_edges() {
+        let triangle = ClosedPath::from_coords(&[(0, 0), (600, 0), (300, 300)]).points;
+        for segment in cardinal_spline(&triangle, 100, true) {
+            assert!(triangle.contains(&segment.control1));
+            assert_eq!(segment.control2, segment.end);
+        }
+    }
+
+    #[test]
+    fn too_few_points_are_not_smoothed() {
+        assert_eq!(cardinal_spline(&ClosedPath::from_coords(&[(0, 0), (600, 0)]).points, 0, true), Vec::new());
+    }
+
+    #[test]
+    fn quadratic_curve_becomes_cubic() {
+        let quad = Segment::Quad(Point { x: 300, y: 0 }, Point { x: 300, y: 300 });
+        assert_eq!(quad.to_cubic(Point { x: 0, y: 0 }), segment((200, 0), (300, 100), (300, 300)));
+    }
+}
@@ -0,0 +1,318 @@
+use std::ops::Range;
+
+use crate::{CanvasConfig, ClosedPath, Point};
+
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum MirrorAxis {
+    // swaps left and right
+    #[default]
+    Vertical,
+
+    // swaps top and bottom
+    Horizontal,
+}
+impl MirrorAxis {
+    pub fn other(&self) -> Self {
+        match self {
+            Self::Vertical => Self::Horizontal,
+            Self::Horizontal => Self::Vertical,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Vertical => "vertical",
+            Self::Horizontal => "horizontal",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Vertical, Self::Horizontal]
+            .into_iter()
+            .find(|axis| axis.name() == name)
+    }
+}
+
+
+impl Point {
+    pub fn mirrored(&self, axis: MirrorAxis, grid_count: Point, canvas: CanvasConfig) -> Point {
+        match axis {
+            MirrorAxis::Vertical => Point {
+                x: grid_count.x * canvas.horizontal_factor - self.x,
+                y: self.y,
+            },
+            MirrorAxis::Horizontal => Point {
+                x: self.x,
+                y: grid_count.y * canvas.vertical_factor - self.y,
+            },
+        }
+    }
+}
+
+
+impl ClosedPath {
+    // makes exports stable and comparable; open paths, paths with several subpaths and paths with
+    // curves are left alone
+    pub fn normalize_start(&mut self) {
+        if !self.closed || self.subpath_starts.len() > 0 || self.control_points.len() > 0 {
+            return;
+        }
+        let start_index = self.points.iter()
+            .enumerate()
+            .min_by_key(|(_i, point)| (point.y, point.x))
+            .map(|(i, _point)| i);
+        if let Some(start_index) = start_index {
+            self.points.rotate_left(start_index);
+        }
+    }
+
+    pub fn reverse(&mut self) {
+        for range in self.subpath_ranges() {
+            self.reverse_subpath(range);
+        }
+    }
+
+    // turns a hole filled under the nonzero rule into a cut-out or vice versa; the first point of a
+    // closed subpath stays in place
+    pub fn reverse_subpath(&mut self, range: Range<usize>) {
+        let start = if self.closed { range.start + 1 } else { range.start };
+        if range.end <= start {
+            return;
+        }
+        self.points[start..range.end].reverse();
+        for control_index in &mut self.control_points {
+            if (start..range.end).contains(control_index) {
+                *control_index = start + range.end - 1 - *control_index;
+            }
+        }
+        self.control_points.sort_unstable();
+    }
+
+    pub fn mirror(&mut self, axis: MirrorAxis, grid_count: Point, canvas: CanvasConfig) {
+        for point in &mut self.points {
+            *point = point.mirrored(axis, grid_count, canvas);
+        }
+        if self.cell {
+            // cells are exported assuming they start at the top-left corner
+            self.normalize_start();
+        }
+    }
+}
+
+
+fn combined_bounding_box(paths: &[ClosedPath], indexes: &[usize]) -> Option<(Point, Point)> {
+    indexes.iter()
+        .filter_map(|&path_index| paths[path_index].bounding_box())
+        .reduce(|(min, max), (path_min, path_max)| (
+            Point { x: min.x.min(path_min.x), y: min.y.min(path_min.y) },
+            Point { x: max.x.max(path_max.x), y: max.y.max(path_max.y) },
+        ))
+}
+
+
+// keeps the paths relative to each other and never moves them past the top or left edge
+pub(crate) fn translate_paths(paths: &mut [ClosedPath], indexes: &[usize], dx: i32, dy: i32) {
+    for &path_index in indexes {
+        for point in &mut paths[path_index].points {
+            point.x += dx;
+            point.y += dy;
+        }
+    }
+    keep_non_negative(paths, indexes);
+}
+
+
+// around the center of their combined bounding box, so that they turn as one shape
+pub(crate) fn rotate_paths_90(paths: &mut [ClosedPath], indexes: &[usize], clockwise: bool) {
+    let (min, max) = match combined_bounding_box(paths, indexes) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+    let center = Point {
+        x: (min.x + max.x) / 2,
+        y: (min.y + max.y) / 2,
+    };
+    for &path_index in indexes {
+        let path = &mut paths[path_index];
+        for point in &mut path.points {
+            let (dx, dy) = (point.x - center.x, point.y - center.y);
+            // y grows downward, so clockwise turns right into down
+            *point = if clockwise {
+                Point { x: center.x - dy, y: center.y + dx }
+            } else {
+                Point { x: center.x + dy, y: center.y - dx }
+            };
+        }
+        if path.cell {
+            // cells are exported assuming they start at the top-left corner
+            path.normalize_start();
+        }
+    }
+    keep_non_negative(paths, indexes);
+}
+
+
+fn keep_non_negative(paths: &mut [ClosedPath], indexes: &[usize]) {
+    let (min, _max) = match combined_bounding_box(paths, indexes) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+    let shift = Point {
+        x: (-min.x).max(0),
+        y: (-min.y).max(0),
+    };
+    for &path_index in indexes {
+        for point in &mut paths[path_index].points {
+            point.x += shift.x;
+            point.y += shift.y;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    // each with its endpoints in ascending order
+    fn edges(path: &ClosedPath) -> BTreeSet<(Point, Point)> {
+        (0..path.points.len())
+            .map(|i| {
+                let a = path.points[i];
+                let b = path.points[(i + 1) % path.points.len()];
+                (a.min(b), a.max(b))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mirroring_reflects_across_the_center_of_the_grid() {
+        let canvas = CanvasConfig::default();
+        let point = Point { x: 100, y: 40 };
+        // odd and even numbers of cells
+        assert_eq!(point.mirrored(MirrorAxis::Vertical, Point { x: 3, y: 3 }, canvas), Point { x: 200, y: 40 });
+        assert_eq!(point.mirrored(MirrorAxis::Vertical, Point { x: 4, y: 3 }, canvas), Point { x: 300, y: 40 });
+        assert_eq!(point.mirrored(MirrorAxis::Horizontal, Point { x: 3, y: 1 }, canvas), Point { x: 100, y: 60 });
+
+        // the center line falls between coordinates if a cell is an odd number of them wide
+        let odd_canvas = CanvasConfig { horizontal_factor: 5, ..canvas };
+        assert_eq!(Point { x: 7, y: 0 }.mirrored(MirrorAxis::Vertical, Point { x: 3, y: 3 }, odd_canvas), Point { x: 8, y: 0 });
+
+        let mut mirrored = ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 200)]);
+        let original = mirrored.clone();
+        mirrored.mirror(MirrorAxis::Vertical, Point { x: 3, y: 3 }, canvas);
+        assert_eq!(mirrored.points, ClosedPath::from_coords(&[(300, 0), (200, 0), (200, 200)]).points);
+        mirrored.mirror(MirrorAxis::Vertical, Point { x: 3, y: 3 }, canvas);
+        assert_eq!(mirrored, original);
+    }
+
+    #[test]
+    fn cells_mirrored_in_place_keep_their_corners() {
+        let canvas = CanvasConfig::default();
+        let grid = Point { x: 3, y: 3 };
+        for axis in [MirrorAxis::Vertical, MirrorAxis::Horizontal] {
+            let mut cell = ClosedPath::new_cell(Point { x: 0, y: 100 }, canvas, 0);
+            cell.mirror(axis, grid, canvas);
+            let (top_left, bottom_right) = cell.bounding_box().unwrap();
+            assert_eq!((cell.points[0], cell.points[2]), (top_left, bottom_right));
+        }
+    }
+
+    #[test]
+    fn selected_paths_move_together() {
+        let mut paths = vec![
+            ClosedPath::from_coords(&[(100, 100), (200, 100), (200, 200)]),
+            ClosedPath::from_coords(&[(0, 0), (50, 0), (50, 50)]),
+            ClosedPath::from_coords(&[(300, 300), (400, 300), (400, 400)]),
+        ];
+        let unselected = paths[1].clone();
+        translate_paths(&mut paths, &[0, 2], 50, -100);
+        assert_eq!(paths[0].points, ClosedPath::from_coords(&[(150, 0), (250, 0), (250, 100)]).points);
+        assert_eq!(paths[2].points, ClosedPath::from_coords(&[(350, 200), (450, 200), (450, 300)]).points);
+        assert_eq!(paths[1], unselected);
+
+        // stopping at the top edge keeps the paths' positions relative to each other
+        translate_paths(&mut paths, &[0, 2], 0, -100);
+        assert_eq!(paths[0].points, ClosedPath::from_coords(&[(150, 0), (250, 0), (250, 100)]).points);
+        assert_eq!(paths[2].points, ClosedPath::from_coords(&[(350, 200), (450, 200), (450, 300)]).points);
+    }
+
+    #[test]
+    fn four_quarter_turns_restore_a_square() {
+        let square = ClosedPath::from_coords(&[(100, 100), (300, 100), (300, 300), (100, 300)]);
+        for clockwise in [true, false] {
+            let mut paths = vec![square.clone()];
+            rotate_paths_90(&mut paths, &[0], clockwise);
+            assert_eq!(edges(&paths[0]), edges(&square));
+            for _ in 0..3 {
+                rotate_paths_90(&mut paths, &[0], clockwise);
+            }
+            assert_eq!(paths[0], square);
+        }
+    }
+
+    #[test]
+    fn rotation_keeps_coordinates_non_negative() {
+        // turning around the center (200, 50) takes the path up to y = -150
+        let mut paths = vec![ClosedPath::from_coords(&[(0, 0), (400, 0), (400, 100)])];
+        rotate_paths_90(&mut paths, &[0], true);
+        assert_eq!(paths[0].points, ClosedPath::from_coords(&[(250, 0), (250, 400), (150, 400)]).points);
+    }
+
+    #[test]
+    fn reversing_twice_restores_the_order() {
+        let mut holed = ClosedPath::from_coords(&[
+            (0, 0), (400, 0), (400, 400), (0, 400), (100, 100), (300, 100), (300, 300),
+        ]);
+        holed.subpath_starts = vec![4];
+        holed.control_points = vec![2];
+        let original = holed.clone();
+
+        holed.reverse();
+        // the first point of each closed subpath stays in place
+        assert_eq!(holed.points, ClosedPath::from_coords(&[
+            (0, 0), (0, 400), (400, 400), (400, 0), (100, 100), (300, 300), (300, 100),
+        ]).points);
+        assert_eq!(holed.control_points, vec![2]);
+        holed.reverse();
+        assert_eq!(holed, original);
+    }
+
+    #[test]
+    fn reversing_a_subpath_leaves_the_others_alone() {
+        let mut holed = ClosedPath::from_coords(&[(0, 0), (400, 0), (400, 400), (100, 100), (300, 100), (300, 300)]);
+        holed.subpath_starts = vec![3];
+        holed.reverse_subpath(3..6);
+        assert_eq!(holed.points, ClosedPath::from_coords(&[
+            (0, 0), (400, 0), (400, 400), (100, 100), (300, 300), (300, 100),
+        ]).points);
+
+        // an open path runs the other way from its last point
+        let mut open = ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100)]);
+        open.closed = false;
+        open.control_points = vec![1];
+        open.reverse_subpath(0..3);
+        assert_eq!(open.points, ClosedPath::from_coords(&[(100, 100), (100, 0), (0, 0)]).points);
+        assert_eq!(open.control_points, vec![1]);
+    }
+
+    #[test]
+    fn normalize_start_keeps_the_edges_of_a_closed_path() {
+        let mut closed = ClosedPath::from_coords(&[(200, 300), (100, 100), (300, 100), (300, 200)]);
+        let original_edges = edges(&closed);
+        closed.normalize_start();
+        assert_eq!(closed.points[0], Point { x: 100, y: 100 });
+        assert_eq!(edges(&closed), original_edges);
+    }
+
+    #[test]
+    fn normalize_start_leaves_an_open_path_alone() {
+        let mut open = ClosedPath::from_coords(&[(200, 300), (100, 100), (300, 100)]);
+        open.closed = false;
+        let original = open.clone();
+        open.normalize_start();
+        assert_eq!(open, original);
+    }
+}
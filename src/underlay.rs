@@ -0,0 +1,152 @@
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+
+use once_cell::sync::Lazy;
+use windows::Win32::Foundation::RECT;
+use windows::Win32::Graphics::Gdi::{AC_SRC_ALPHA, AC_SRC_OVER, AlphaBlend, BLENDFUNCTION, HDC};
+
+use crate::Point;
+use crate::gdi_primitives::{make_dib_section, make_memory_dc, select_object_scoped, OwnedBitmap};
+
+
+const DEFAULT_OPACITY: u8 = 0x80;
+const MIN_SCALE_PERCENT: i32 = 10;
+// keeps the scaled size of the image within an i32 even on the largest grid
+const MAX_SCALE_PERCENT: i32 = 400;
+
+// beyond this, the copy is stretched whenever it is drawn so zooming in does not use up the memory
+const MAX_SCALED_SIDE: i32 = 4096;
+
+
+// scaling is what makes drawing the image slow, so it is only redone when the size changes
+static SCALED_BITMAP: Lazy<Mutex<Option<(Point, OwnedBitmap)>>> = Lazy::new(|| Mutex::new(None));
+
+
+pub(crate) fn forget_scaled_bitmap() {
+    *SCALED_BITMAP.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+
+// the source must contain premultiplied alpha
+fn blend(target: HDC, dest: RECT, source: HDC, source_size: Point, opacity: u8) {
+    let blend = BLENDFUNCTION {
+        BlendOp: AC_SRC_OVER as u8,
+        BlendFlags: 0,
+        SourceConstantAlpha: opacity,
+        AlphaFormat: AC_SRC_ALPHA as u8,
+    };
+    unsafe {
+        AlphaBlend(
+            target,
+            dest.left,
+            dest.top,
+            dest.right - dest.left,
+            dest.bottom - dest.top,
+            source,
+            0,
+            0,
+            source_size.x,
+            source_size.y,
+            blend,
+        )
+    };
+}
+
+
+// displayed behind the drawing to trace over, never exported
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Underlay {
+    pub width: u32,
+    pub height: u32,
+
+    // top-down, premultiplied BGRA
+    pub pixels: Vec<u8>,
+
+    pub opacity: u8,
+    pub visible: bool,
+
+    // in drawing coordinates
+    pub offset: Point,
+
+    // relative to the grid, or to the natural size without a grid
+    pub scale_percent: i32,
+    pub locked: bool,
+}
+impl Underlay {
+    pub fn load(path: &Path) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.to_rgba8();
+        let (width, height) = image.dimensions();
+        let mut pixels = Vec::with_capacity(image.as_raw().len());
+        for rgba in image.pixels() {
+            let [r, g, b, a] = rgba.0;
+            let premultiply = |channel: u8| ((u16::from(channel) * u16::from(a)) / 255) as u8;
+            pixels.extend_from_slice(&[premultiply(b), premultiply(g), premultiply(r), a]);
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+            opacity: DEFAULT_OPACITY,
+            visible: true,
+            offset: Point::default(),
+            scale_percent: 100,
+            locked: false,
+        })
+    }
+
+    pub fn rescale(&mut self, delta_percent: i32) {
+        self.scale_percent = (self.scale_percent + delta_percent).clamp(MIN_SCALE_PERCENT, MAX_SCALE_PERCENT);
+    }
+
+    pub fn draw(&self, hdc: HDC, dest: RECT) {
+        if !self.visible || self.width == 0 || self.height == 0 {
+            return;
+        }
+        if dest.right <= dest.left || dest.bottom <= dest.top {
+            return;
+        }
+        let scaled_size = Point {
+            x: (dest.right - dest.left).min(MAX_SCALED_SIDE),
+            y: (dest.bottom - dest.top).min(MAX_SCALED_SIDE),
+        };
+
+        let mut scaled_guard = SCALED_BITMAP.lock().unwrap_or_else(PoisonError::into_inner);
+        let reusable = scaled_guard.as_ref()
+            .is_some_and(|(size, bitmap)| *size == scaled_size && !bitmap.handle().is_invalid());
+        if !reusable {
+            *scaled_guard = Some((scaled_size, self.scaled_bitmap(hdc, scaled_size)));
+        }
+        let (_size, scaled) = scaled_guard.as_ref().unwrap();
+        if scaled.handle().is_invalid() {
+            return;
+        }
+
+        let memory_dc = make_memory_dc(hdc);
+        let _bitmap_guard = select_object_scoped(memory_dc.handle(), scaled.handle(), "reference image");
+        blend(hdc, dest, memory_dc.handle(), scaled_size, self.opacity);
+    }
+
+    fn scaled_bitmap(&self, hdc: HDC, size: Point) -> OwnedBitmap {
+        let width: i32 = self.width.try_into().unwrap();
+        let height: i32 = self.height.try_into().unwrap();
+        let (original, bits) = make_dib_section(hdc, width, height);
+        if bits.is_null() {
+            return original;
+        }
+        unsafe { std::ptr::copy_nonoverlapping(self.pixels.as_ptr(), bits, self.pixels.len()) };
+
+        let (scaled, _scaled_bits) = make_dib_section(hdc, size.x, size.y);
+        if scaled.handle().is_invalid() {
+            return scaled;
+        }
+
+        // the guards select the bitmaps out of the DCs before the DCs are deleted
+        let source_dc = make_memory_dc(hdc);
+        let _source_guard = select_object_scoped(source_dc.handle(), original.handle(), "reference image");
+        let target_dc = make_memory_dc(hdc);
+        let _target_guard = select_object_scoped(target_dc.handle(), scaled.handle(), "scaled reference image");
+        let whole = RECT { left: 0, top: 0, right: size.x, bottom: size.y };
+        blend(target_dc.handle(), whole, source_dc.handle(), Point { x: width, y: height }, 0xFF);
+        scaled
+    }
+}
@@ -0,0 +1,102 @@
+use crate::Point;
+
+
+const MAX_MAGNIFICATION: i32 = 8;
+const MAX_REDUCTION: i32 = 16;
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Zoom {
+    pub numerator: i32,
+    pub denominator: i32,
+}
+impl Zoom {
+    pub fn scale(&self, value: i32) -> i32 {
+        (value * self.numerator) / self.denominator
+    }
+
+    pub fn unscale(&self, value: i32) -> i32 {
+        (value * self.denominator) / self.numerator
+    }
+
+    pub fn zoom_in(&mut self) {
+        if self.denominator > 1 {
+            self.denominator /= 2;
+        } else if self.numerator < MAX_MAGNIFICATION {
+            self.numerator *= 2;
+        }
+    }
+
+    pub fn zoom_out(&mut self) {
+        if self.numerator > 1 {
+            self.numerator /= 2;
+        } else if self.denominator < MAX_REDUCTION {
+            self.denominator *= 2;
+        }
+    }
+
+    pub fn percent(&self) -> i32 {
+        100 * self.numerator / self.denominator
+    }
+
+    // the maximum reduction if the content does not fit at all
+    pub fn fitting(content: Point, available: Point) -> Zoom {
+        let mut zoom = Zoom {
+            numerator: 1,
+            denominator: MAX_REDUCTION,
+        };
+        loop {
+            let mut larger = zoom;
+            larger.zoom_in();
+            if larger == zoom || larger.scale(content.x) > available.x || larger.scale(content.y) > available.y {
+                return zoom;
+            }
+            zoom = larger;
+        }
+    }
+}
+impl Default for Zoom {
+    fn default() -> Self {
+        // half size
+        Self {
+            numerator: 1,
+            denominator: 2,
+        }
+    }
+}
+
+
+// what happens to the view when the window is resized
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum ResizeMode {
+    // the drawing stays put relative to the top left corner
+    #[default]
+    Anchor,
+    Center,
+
+    // the grid is also zoomed to fill the window
+    Fit,
+}
+impl ResizeMode {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Anchor => Self::Center,
+            Self::Center => Self::Fit,
+            Self::Fit => Self::Anchor,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Anchor => "anchor",
+            Self::Center => "center",
+            Self::Fit => "fit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Anchor, Self::Center, Self::Fit]
+            .into_iter()
+            .find(|mode| mode.name() == name)
+    }
+}
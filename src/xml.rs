@@ -1,12 +1,345 @@
-use std::fmt::Write as _;
+use std::collections::BTreeSet;
+use std::fmt;
 
-use crate::{ClosedPath, HORIZONTAL_FACTOR, Point, VERTICAL_FACTOR};
+use sxd_document::dom::{ChildOfElement, ChildOfRoot, Element};
+
+use crate::{CanvasConfig, ClosedPath, GridLimit, MAX_GRID_LIMIT, Point};
+use crate::spline::{cardinal_spline, Segment};
 
 
 const SVG_NS_URI: &str = "http://www.w3.org/2000/svg";
+const METADATA_PREFIX: &str = "data-pixelpath-";
+
+// what viewers use if no color is given
+const DEFAULT_COLOR: u32 = 0x000000;
+
+// a tenth of a grid cell
+const STROKE_WIDTH: i32 = 10;
+const CELL_UNIT_STROKE_PRECISION: usize = 3;
+
+// larger grids are exported with their size clamped; the view box still covers the whole grid,
+// so the drawing is scaled down instead of cut off
+const MAX_DIMENSION: i32 = 1_000_000;
+
+// drawing coordinates are integers and grid cells are at most a few thousand of them wide
+pub(crate) const MAX_PRECISION: usize = 6;
+
+
+// exported instead of the grid
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct ExportFrame {
+    // in drawing coordinates
+    pub origin: Point,
+    pub size: Point,
+
+    // of the exported document
+    pub output_size: Point,
+}
+
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum SvgStyle {
+    // what viewers do if no style is given
+    #[default]
+    Filled,
+    Outline,
+    FilledOutline,
+}
+impl SvgStyle {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Filled => Self::Outline,
+            Self::Outline => Self::FilledOutline,
+            Self::FilledOutline => Self::Filled,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Filled => "filled",
+            Self::Outline => "outline",
+            Self::FilledOutline => "filled-outline",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Filled, Self::Outline, Self::FilledOutline]
+            .into_iter()
+            .find(|style| style.name() == name)
+    }
+
+    fn fills(&self) -> bool {
+        *self != Self::Outline
+    }
+
+    fn strokes(&self) -> bool {
+        *self != Self::Filled
+    }
+
+    fn attributes(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Filled => &[],
+            Self::Outline => &[("fill", "none"), ("stroke", "black")],
+            Self::FilledOutline => &[("fill", "black"), ("stroke", "black")],
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum FillRule {
+    // nested subpaths become holes regardless of their direction; also what GDI does by default
+    #[default]
+    EvenOdd,
+
+    // subpaths winding in opposite directions cancel out; what SVG viewers do if no rule is given
+    NonZero,
+}
+impl FillRule {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::EvenOdd => Self::NonZero,
+            Self::NonZero => Self::EvenOdd,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::EvenOdd => "evenodd",
+            Self::NonZero => "nonzero",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::EvenOdd, Self::NonZero]
+            .into_iter()
+            .find(|rule| rule.name() == name)
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct SvgOptions {
+    pub crisp_edges: bool,
+
+    // the grid is exported if `None`
+    pub frame: Option<ExportFrame>,
+    pub style: SvgStyle,
+    pub fill_rule: FillRule,
+
+    // fractional digits, up to MAX_PRECISION
+    pub precision: usize,
+
+    // in grid cells; points between grid lines need a nonzero `precision` to be exported faithfully
+    pub cell_units: bool,
+
+    // an `id` from the label of the path or, failing that, `path-N`
+    pub path_ids: bool,
+
+    // a rectangle painted behind the shapes; transparent if `None`
+    pub background: Option<u32>,
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub(crate) enum PathToken {
+    Command(char),
+    Number(f64),
+}
+
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum ParseError {
+    Xml(String),
+
+    // the root is not an `svg` element with a numeric `width` and `height`
+    NotSvg,
+
+    // a command that PixelPath does not export
+    UnsupportedCommand(char),
+    InvalidCoordinates,
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Xml(message) => write!(f, "invalid XML: {}", message),
+            Self::NotSvg => write!(f, "not an SVG document with a width and height"),
+            Self::UnsupportedCommand(command) => write!(f, "unsupported path command {:?}", command),
+            Self::InvalidCoordinates => write!(f, "missing or malformed coordinates"),
+        }
+    }
+}
+impl std::error::Error for ParseError {}
 
 
-pub(crate) fn assemble_svg(grid: Point, paths: &[ClosedPath]) -> String {
+// viewers ignore the `data-pixelpath-*` attributes, but they let PixelPath restore the path
+// faithfully
+fn set_metadata(elem: Element, path: &ClosedPath) {
+    let metadata = [
+        ("closed", if path.closed { "true" } else { "false" }),
+        ("cell", if path.cell { "true" } else { "false" }),
+    ];
+    for (key, value) in metadata {
+        elem.set_attribute_value(format!("{}{}", METADATA_PREFIX, key).as_str(), value);
+    }
+    if path.smooth && path.control_points.len() == 0 {
+        // the curves can be computed again from the points between them (explicit control
+        // points take precedence over smoothing, so it does not matter otherwise)
+        elem.set_attribute_value(format!("{}smooth", METADATA_PREFIX).as_str(), &path.tension_percent.to_string());
+    }
+}
+
+
+// the subpaths are kept apart
+fn without_control_points(path: ClosedPath) -> ClosedPath {
+    let kept_before = |index: usize| (0..index)
+        .filter(|i| !path.control_points.contains(i))
+        .count();
+    let subpath_starts = path.subpath_starts.iter()
+        .map(|&start| kept_before(start))
+        .collect();
+    let points = path.points.iter()
+        .enumerate()
+        .filter(|(i, _point)| !path.control_points.contains(i))
+        .map(|(_i, point)| *point)
+        .collect();
+    ClosedPath {
+        points,
+        subpath_starts,
+        control_points: Vec::new(),
+        ..path
+    }
+}
+
+
+// differs from all the `used_ids` and is then added to them
+fn shape_id(path_index: usize, label: Option<&str>, used_ids: &mut BTreeSet<String>) -> String {
+    let base = match label.filter(|label| label.len() > 0) {
+        Some(label) => {
+            // keep to characters that are valid in an XML name
+            let mut base: String = label.chars()
+                .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+                .collect();
+            if !base.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+                base.insert(0, '_');
+            }
+            base
+        },
+        None => format!("path-{}", path_index),
+    };
+
+    let mut id = base.clone();
+    let mut suffix = 2;
+    while used_ids.contains(&id) {
+        id = format!("{}-{}", base, suffix);
+        suffix += 1;
+    }
+    used_ids.insert(id.clone());
+    id
+}
+
+
+pub(crate) fn css_color(color: u32) -> String {
+    let red = color & 0xFF;
+    let green = (color >> 8) & 0xFF;
+    let blue = (color >> 16) & 0xFF;
+    format!("#{:02x}{:02x}{:02x}", red, green, blue)
+}
+
+
+fn set_style(elem: Element, style: SvgStyle, color: u32, stroke_width: &str) {
+    for &(key, value) in style.attributes() {
+        elem.set_attribute_value(key, value);
+    }
+    if style.strokes() {
+        elem.set_attribute_value("stroke-width", stroke_width);
+    }
+    if color != DEFAULT_COLOR {
+        // paint the shape in its own color instead of black
+        let key = if style.fills() { "fill" } else { "stroke" };
+        elem.set_attribute_value(key, &css_color(color));
+    }
+}
+
+
+// trailing zeroes are dropped
+fn format_coordinate(value: f64, precision: usize) -> String {
+    let mut formatted = format!("{:.*}", precision, value);
+    if formatted.contains('.') {
+        let trimmed_length = formatted.trim_end_matches('0').trim_end_matches('.').len();
+        formatted.truncate(trimmed_length);
+    }
+    if formatted == "-0" {
+        formatted = String::from("0");
+    }
+    formatted
+}
+
+
+fn content_bounds(grid_size: Point, paths: &[ClosedPath]) -> (Point, Point) {
+    let mut min = Point::default();
+    let mut max = grid_size;
+    for (path_min, path_max) in paths.iter().filter_map(|path| path.bounding_box()) {
+        min.x = min.x.min(path_min.x);
+        min.y = min.y.min(path_min.y);
+        max.x = max.x.max(path_max.x);
+        max.y = max.y.max(path_max.y);
+    }
+    (min, max)
+}
+
+
+// coordinates are measured from the top-left corner of the canvas even if the grid lines have
+// been shifted; the grid origin only affects editing
+pub(crate) fn assemble_svg(
+    grid: Point,
+    canvas: CanvasConfig,
+    paths: &[ClosedPath],
+    options: &SvgOptions,
+) -> String {
+    assemble_document(grid, canvas, paths, |_path_index| true, options)
+}
+
+
+// cropped to the bounding box of the paths, which replaces any export frame; the shapes keep
+// the ids they have in the full document
+pub(crate) fn assemble_svg_subset(
+    grid: Point,
+    canvas: CanvasConfig,
+    paths: &[ClosedPath],
+    path_indexes: &BTreeSet<usize>,
+    options: &SvgOptions,
+) -> String {
+    let bounds = path_indexes.iter()
+        .filter_map(|&path_index| paths.get(path_index)?.bounding_box())
+        .reduce(|(min, max), (path_min, path_max)| (
+            Point { x: min.x.min(path_min.x), y: min.y.min(path_min.y) },
+            Point { x: max.x.max(path_max.x), y: max.y.max(path_max.y) },
+        ));
+    let (min, max) = bounds.unwrap_or_default();
+    let size = Point {
+        x: (max.x - min.x).max(1),
+        y: (max.y - min.y).max(1),
+    };
+    let subset_options = SvgOptions {
+        frame: Some(ExportFrame {
+            origin: min,
+            size,
+            output_size: size,
+        }),
+        ..*options
+    };
+    assemble_document(grid, canvas, paths, |path_index| path_indexes.contains(&path_index), &subset_options)
+}
+
+
+fn assemble_document<F: Fn(usize) -> bool>(
+    grid: Point,
+    canvas: CanvasConfig,
+    paths: &[ClosedPath],
+    included: F,
+    options: &SvgOptions,
+) -> String {
     let doc_package = sxd_document::Package::new();
     let doc = doc_package.as_document();
 
@@ -14,29 +347,119 @@ pub(crate) fn assemble_svg(grid: Point, paths: &[ClosedPath]) -> String {
     svg_elem.set_attribute_value("xmlns", SVG_NS_URI);
     doc.root().append_child(svg_elem);
 
-    // use grid to determine dimensions
-    let width = grid.x * HORIZONTAL_FACTOR;
-    let height = grid.y * VERTICAL_FACTOR;
-    svg_elem.set_attribute_value("width", &format!("{}", width));
-    svg_elem.set_attribute_value("height", &format!("{}", height));
+    // in cell units, the view box shrinks with the coordinates while the document keeps its size
+    let (x_unit, y_unit) = coordinate_units(canvas, options);
+    let stroke_width = if options.cell_units {
+        let stroke_width = f64::from(STROKE_WIDTH) / f64::from(canvas.horizontal_factor);
+        format_coordinate(stroke_width, CELL_UNIT_STROKE_PRECISION)
+    } else {
+        STROKE_WIDTH.to_string()
+    };
+    let coord_x = |value: i64| format_coordinate(value as f64 / x_unit, options.precision);
+    let coord_y = |value: i64| format_coordinate(value as f64 / y_unit, options.precision);
+
+    let (view_x, view_y, view_width, view_height): (i64, i64, i64, i64) = if let Some(frame) = &options.frame {
+        // the frame determines dimensions; everything outside it is clipped
+        svg_elem.set_attribute_value("width", &format!("{}", frame.output_size.x));
+        svg_elem.set_attribute_value("height", &format!("{}", frame.output_size.y));
+        (frame.origin.x.into(), frame.origin.y.into(), frame.size.x.into(), frame.size.y.into())
+    } else {
+        // use grid to determine dimensions; a document without an area cannot be displayed, so
+        // a grid without cells counts as a single cell
+        let width = grid.x.max(1).saturating_mul(canvas.horizontal_factor);
+        let height = grid.y.max(1).saturating_mul(canvas.vertical_factor);
+        svg_elem.set_attribute_value("width", &format!("{}", width.clamp(0, MAX_DIMENSION)));
+        svg_elem.set_attribute_value("height", &format!("{}", height.clamp(0, MAX_DIMENSION)));
+
+        // make sure points outside the grid are not clipped
+        // (computed in i64 as the extremes may be more than i32::MAX apart)
+        let (min, max) = content_bounds(Point { x: width, y: height }, paths);
+        let view_width = i64::from(max.x) - i64::from(min.x);
+        let view_height = i64::from(max.y) - i64::from(min.y);
+        (min.x.into(), min.y.into(), view_width, view_height)
+    };
+    svg_elem.set_attribute_value(
+        "viewBox",
+        &format!(
+            "{} {} {} {}",
+            coord_x(view_x), coord_y(view_y), coord_x(view_width), coord_y(view_height),
+        ),
+    );
+
+    if options.crisp_edges {
+        svg_elem.set_attribute_value("shape-rendering", "crispEdges");
+    }
+    if options.cell_units {
+        // allows parse_svg to scale the coordinates back
+        svg_elem.set_attribute_value(format!("{}units", METADATA_PREFIX).as_str(), "cells");
+    }
+    // inherited by all shapes
+    svg_elem.set_attribute_value("fill-rule", options.fill_rule.name());
+
+    if let Some(background) = options.background {
+        // cover the whole view box, behind all shapes
+        let background_elem = doc.create_element("rect");
+        background_elem.set_attribute_value("x", &coord_x(view_x));
+        background_elem.set_attribute_value("y", &coord_y(view_y));
+        background_elem.set_attribute_value("width", &coord_x(view_width));
+        background_elem.set_attribute_value("height", &coord_y(view_height));
+        background_elem.set_attribute_value("fill", &css_color(background));
+        background_elem.set_attribute_value(format!("{}background", METADATA_PREFIX).as_str(), "true");
+        svg_elem.append_child(background_elem);
+    }
 
-    let mut full_path_def = String::new();
-    for path in paths {
+    let x = |value: i32| coord_x(value.into());
+    let y = |value: i32| coord_y(value.into());
+    let mut used_ids = BTreeSet::new();
+    for (path_index, path) in paths.iter().enumerate() {
         if path.points.len() == 0 {
             continue;
         }
+        // assign ids to excluded paths too so that the ids don't depend on what is exported
+        let id = if options.path_ids {
+            Some(shape_id(path_index, path.label.as_deref(), &mut used_ids))
+        } else {
+            None
+        };
+        if !included(path_index) {
+            continue;
+        }
 
-        for (i, point) in path.points.iter().enumerate() {
-            let spacing = if full_path_def.len() == 0 { "" } else { " " };
-            let prefix = if i == 0 { "M" } else { "L" };
-            write!(full_path_def, "{}{} {} {}", spacing, prefix, point.x, point.y).unwrap();
+        if path.cell && path.points.len() == 4 {
+            // painted cells are exported as rectangles
+            let top_left = path.points[0];
+            let bottom_right = path.points[2];
+            let rect_elem = doc.create_element("rect");
+            rect_elem.set_attribute_value("x", &x(top_left.x));
+            rect_elem.set_attribute_value("y", &y(top_left.y));
+            rect_elem.set_attribute_value("width", &x(bottom_right.x - top_left.x));
+            rect_elem.set_attribute_value("height", &y(bottom_right.y - top_left.y));
+            set_style(rect_elem, options.style, path.color, &stroke_width);
+            set_metadata(rect_elem, path);
+            if let Some(id) = &id {
+                rect_elem.set_attribute_value("id", id);
+            }
+            svg_elem.append_child(rect_elem);
+            continue;
         }
-        write!(full_path_def, " z").unwrap();
-    }
 
-    let path_elem = doc.create_element("path");
-    path_elem.set_attribute_value("d", &full_path_def);
-    svg_elem.append_child(path_elem);
+        let path_def = path_data(path, &x, &y);
+
+        // one element per path keeps the metadata attached to the right shape
+        let path_elem = doc.create_element("path");
+        path_elem.set_attribute_value("d", &path_def);
+        if path.closed {
+            set_style(path_elem, options.style, path.color, &stroke_width);
+        } else {
+            // filling an open path would look as if it were closed
+            set_style(path_elem, SvgStyle::Outline, path.color, &stroke_width);
+        }
+        set_metadata(path_elem, path);
+        if let Some(id) = &id {
+            path_elem.set_attribute_value("id", id);
+        }
+        svg_elem.append_child(path_elem);
+    }
 
     let mut ret = Vec::new();
     sxd_document::writer::format_document(&doc, &mut ret)
@@ -44,3 +467,688 @@ pub(crate) fn assemble_svg(grid: Point, paths: &[ClosedPath]) -> String {
     String::from_utf8(ret)
         .expect("XML serialized into something that is not UTF-8")
 }
+
+
+fn coordinate_units(canvas: CanvasConfig, options: &SvgOptions) -> (f64, f64) {
+    if options.cell_units {
+        (f64::from(canvas.horizontal_factor), f64::from(canvas.vertical_factor))
+    } else {
+        (1.0, 1.0)
+    }
+}
+
+
+// each subpath gets its own `M ... z` so that holes can be cut out of the shape
+fn path_data<X: Fn(i32) -> String, Y: Fn(i32) -> String>(path: &ClosedPath, x: &X, y: &Y) -> String {
+    let mut commands = Vec::new();
+    for range in path.subpath_ranges() {
+        let subpath = &path.points[range.clone()];
+        let spline = if path.smooth && path.control_points.len() == 0 {
+            cardinal_spline(subpath, path.tension_percent, path.closed)
+        } else {
+            Vec::new()
+        };
+        commands.push(format!("M {} {}", x(subpath[0].x), y(subpath[0].y)));
+        if spline.len() > 0 {
+            for segment in &spline {
+                commands.push(format!(
+                    "C {} {} {} {} {} {}",
+                    x(segment.control1.x), y(segment.control1.y),
+                    x(segment.control2.x), y(segment.control2.y),
+                    x(segment.end.x), y(segment.end.y),
+                ));
+            }
+        } else {
+            for segment in path.segments(range) {
+                commands.push(match segment {
+                    Segment::Line(end) => format!("L {} {}", x(end.x), y(end.y)),
+                    Segment::Quad(control, end) => format!(
+                        "Q {} {} {} {}",
+                        x(control.x), y(control.y), x(end.x), y(end.y),
+                    ),
+                    Segment::Cubic(control1, control2, end) => format!(
+                        "C {} {} {} {} {} {}",
+                        x(control1.x), y(control1.y), x(control2.x), y(control2.y), x(end.x), y(end.y),
+                    ),
+                });
+            }
+        }
+        if path.closed {
+            commands.push(String::from("z"));
+        }
+    }
+    commands.join(" ")
+}
+
+
+// all paths in one `d` attribute, coordinates as in assemble_svg; cells become rectangles and
+// colors are lost
+pub(crate) fn build_path_data(canvas: CanvasConfig, paths: &[ClosedPath], options: &SvgOptions) -> String {
+    let (x_unit, y_unit) = coordinate_units(canvas, options);
+    let x = |value: i32| format_coordinate(f64::from(value) / x_unit, options.precision);
+    let y = |value: i32| format_coordinate(f64::from(value) / y_unit, options.precision);
+
+    let path_defs: Vec<String> = paths.iter()
+        .filter(|path| path.points.len() > 0)
+        .map(|path| path_data(path, &x, &y))
+        .collect();
+    path_defs.join(" ")
+}
+
+
+pub(crate) fn tokenize_path_data(data: &str) -> Vec<PathToken> {
+    let mut tokens = Vec::new();
+    let mut chars = data.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_ascii_alphabetic() && c != 'e' && c != 'E' {
+            tokens.push(PathToken::Command(c));
+            chars.next();
+        } else if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' {
+            // a number ends where the next one (or anything else) starts
+            let mut end = start + c.len_utf8();
+            let mut seen_dot = c == '.';
+            let mut previous = c;
+            chars.next();
+            while let Some(&(i, c)) = chars.peek() {
+                let continues = c.is_ascii_digit()
+                    || (c == '.' && !seen_dot)
+                    || c == 'e' || c == 'E'
+                    || ((c == '-' || c == '+') && (previous == 'e' || previous == 'E'));
+                if !continues {
+                    break;
+                }
+                seen_dot |= c == '.';
+                previous = c;
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            if let Ok(number) = data[start..end].parse() {
+                tokens.push(PathToken::Number(number));
+            }
+        } else {
+            // whitespace, commas and anything unexpected
+            chars.next();
+        }
+    }
+    tokens
+}
+
+
+// the grid comes from `width` and `height`, which only matches if there was no frame; smoothed
+// paths with PixelPath's metadata are smoothed again, others come back as curves
+pub(crate) fn parse_svg(svg: &str, canvas: CanvasConfig) -> Result<(Point, Vec<ClosedPath>), ParseError> {
+    let package = sxd_document::parser::parse(svg)
+        .map_err(|e| ParseError::Xml(format!("{:?}", e)))?;
+    let doc = package.as_document();
+    let svg_elem = doc.root().children()
+        .into_iter()
+        .find_map(|child| match child {
+            ChildOfRoot::Element(elem) => Some(elem),
+            _ => None,
+        })
+        .filter(|elem| elem.name().local_part() == "svg")
+        .ok_or(ParseError::NotSvg)?;
+
+    let number = |elem: Element, name: &str| elem.attribute_value(name)
+        .and_then(|value| value.trim().parse::<f64>().ok());
+    let (width, height) = number(svg_elem, "width").zip(number(svg_elem, "height"))
+        .ok_or(ParseError::NotSvg)?;
+    // the saturating float conversion keeps huge sizes in range until they are clamped
+    let grid = GridLimit(MAX_GRID_LIMIT).apply(Point {
+        x: (width / f64::from(canvas.horizontal_factor)).round() as i32,
+        y: (height / f64::from(canvas.vertical_factor)).round() as i32,
+    });
+
+    let in_cells = svg_elem.attribute_value(format!("{}units", METADATA_PREFIX).as_str()) == Some("cells");
+    let (x_unit, y_unit) = if in_cells {
+        (f64::from(canvas.horizontal_factor), f64::from(canvas.vertical_factor))
+    } else {
+        (1.0, 1.0)
+    };
+    let to_point = |x: f64, y: f64| Point {
+        x: (x * x_unit).round() as i32,
+        y: (y * y_unit).round() as i32,
+    };
+
+    let mut paths = Vec::new();
+    for child in svg_elem.children() {
+        let elem = match child {
+            ChildOfElement::Element(elem) => elem,
+            _ => continue,
+        };
+        if elem.attribute_value(format!("{}background", METADATA_PREFIX).as_str()) == Some("true") {
+            // not part of the drawing
+            continue;
+        }
+        let mut path = match elem.name().local_part() {
+            "rect" => {
+                let coordinates = ["x", "y", "width", "height"].map(|name| number(elem, name));
+                let [Some(x), Some(y), Some(width), Some(height)] = coordinates else {
+                    return Err(ParseError::InvalidCoordinates);
+                };
+                let top_left = to_point(x, y);
+                let bottom_right = to_point(x + width, y + height);
+                ClosedPath {
+                    points: vec![
+                        top_left,
+                        Point { x: bottom_right.x, y: top_left.y },
+                        bottom_right,
+                        Point { x: top_left.x, y: bottom_right.y },
+                    ],
+                    cell: true,
+                    ..ClosedPath::default()
+                }
+            },
+            "path" => {
+                let definition = elem.attribute_value("d").ok_or(ParseError::InvalidCoordinates)?;
+                let mut path = parse_path_definition(definition, to_point)?;
+                path.cell = elem.attribute_value(format!("{}cell", METADATA_PREFIX).as_str()) == Some("true");
+                // the metadata also covers paths too short for the `z` to be noticeable
+                match elem.attribute_value(format!("{}closed", METADATA_PREFIX).as_str()) {
+                    Some("true") => path.closed = true,
+                    Some("false") => path.closed = false,
+                    _ => {},
+                }
+                let tension_percent = elem.attribute_value(format!("{}smooth", METADATA_PREFIX).as_str())
+                    .and_then(|value| value.parse().ok());
+                if let Some(tension_percent) = tension_percent {
+                    path = without_control_points(path);
+                    path.smooth = true;
+                    path.tension_percent = tension_percent;
+                }
+                path
+            },
+            // not something we export
+            _ => continue,
+        };
+
+        // the color is in whichever attribute set_style painted
+        let painted = ["fill", "stroke"].into_iter()
+            .filter_map(|key| elem.attribute_value(key))
+            .find(|value| *value != "none");
+        if let Some(color) = painted.and_then(parse_css_color) {
+            path.color = color;
+        }
+
+        // numbered ids are generated anew on export
+        let numbered = |id: &str| id.strip_prefix("path-").is_some_and(|n| n.parse::<usize>().is_ok());
+        path.label = elem.attribute_value("id")
+            .filter(|id| !numbered(id))
+            .map(String::from);
+        paths.push(path);
+    }
+
+    Ok((grid, paths))
+}
+
+
+fn parse_path_definition(
+    definition: &str,
+    to_point: impl Fn(f64, f64) -> Point,
+) -> Result<ClosedPath, ParseError> {
+    let tokens = tokenize_path_data(definition);
+    let mut path = ClosedPath {
+        closed: false,
+        ..ClosedPath::default()
+    };
+    let mut subpath_start = 0;
+    let mut i = 0;
+    while i < tokens.len() {
+        let command = match tokens[i] {
+            PathToken::Command(command) => command,
+            PathToken::Number(_) => return Err(ParseError::InvalidCoordinates),
+        };
+        i += 1;
+
+        let coordinate_count = match command {
+            'M' | 'L' => 2,
+            'Q' => 4,
+            'C' => 6,
+            'z' | 'Z' => 0,
+            other => return Err(ParseError::UnsupportedCommand(other)),
+        };
+        let coordinates: Vec<f64> = tokens[i..].iter()
+            .take(coordinate_count)
+            .map_while(|token| match token {
+                PathToken::Number(number) => Some(*number),
+                PathToken::Command(_) => None,
+            })
+            .collect();
+        if coordinates.len() < coordinate_count {
+            return Err(ParseError::InvalidCoordinates);
+        }
+        i += coordinate_count;
+        let points: Vec<Point> = coordinates.chunks(2)
+            .map(|xy| to_point(xy[0], xy[1]))
+            .collect();
+
+        match command {
+            'M' => {
+                if path.points.len() > 0 {
+                    path.subpath_starts.push(path.points.len());
+                }
+                subpath_start = path.points.len();
+                path.points.push(points[0]);
+            },
+            'z' | 'Z' => {
+                path.closed = true;
+                // a curve closing the subpath ends where it began, which is implied when closed
+                let subpath_length = path.points.len() - subpath_start;
+                if subpath_length > 1 && path.points.last() == Some(&path.points[subpath_start]) {
+                    path.points.pop();
+                }
+            },
+            _ => {
+                // all but the last point of a curve are control points
+                if path.points.len() == 0 {
+                    return Err(ParseError::InvalidCoordinates);
+                }
+                for (point_index, point) in points.iter().enumerate() {
+                    if point_index + 1 < points.len() {
+                        path.control_points.push(path.points.len());
+                    }
+                    path.points.push(*point);
+                }
+            },
+        }
+    }
+
+    if path.points.len() == 0 {
+        return Err(ParseError::InvalidCoordinates);
+    }
+    Ok(path)
+}
+
+
+// as written by css_color, or the keyword `black`
+fn parse_css_color(value: &str) -> Option<u32> {
+    if value == "black" {
+        return Some(DEFAULT_COLOR);
+    }
+    let hex = value.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    let red = (rgb >> 16) & 0xFF;
+    let green = (rgb >> 8) & 0xFF;
+    let blue = rgb & 0xFF;
+    Some(red | (green << 8) | (blue << 16))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn with_root<T, F: FnOnce(Element) -> T>(svg: &str, f: F) -> T {
+        let package = sxd_document::parser::parse(svg).unwrap();
+        let doc = package.as_document();
+        let svg_elem = doc.root().children()
+            .into_iter()
+            .find_map(|child| match child {
+                ChildOfRoot::Element(elem) => Some(elem),
+                _ => None,
+            })
+            .unwrap();
+        f(svg_elem)
+    }
+
+    fn root_attribute(svg: &str, name: &str) -> String {
+        with_root(svg, |svg_elem| String::from(svg_elem.attribute_value(name).unwrap()))
+    }
+
+    // in document order
+    fn path_definitions(svg: &str) -> Vec<String> {
+        with_root(svg, |svg_elem| svg_elem.children()
+            .into_iter()
+            .filter_map(|child| match child {
+                ChildOfElement::Element(elem) => Some(elem),
+                _ => None,
+            })
+            .filter(|elem| elem.name().local_part() == "path")
+            .map(|elem| String::from(elem.attribute_value("d").unwrap()))
+            .collect())
+    }
+
+    // each element below the root, in document order
+    fn shape_attributes(svg: &str, name: &str) -> Vec<(String, Option<String>)> {
+        with_root(svg, |svg_elem| svg_elem.children()
+            .into_iter()
+            .filter_map(|child| match child {
+                ChildOfElement::Element(elem) => Some(elem),
+                _ => None,
+            })
+            .map(|elem| (String::from(elem.name().local_part()), elem.attribute_value(name).map(String::from)))
+            .collect())
+    }
+
+    #[test]
+    fn styles_set_fill_and_stroke() {
+        let paths = [ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100)])];
+        let svg_with_style = |style| {
+            let options = SvgOptions { style, ..SvgOptions::default() };
+            assemble_svg(Point { x: 2, y: 2 }, CanvasConfig::default(), &paths, &options)
+        };
+        let attributes = |svg: &str| ["fill", "stroke", "stroke-width"]
+            .map(|name| shape_attributes(svg, name)[0].1.clone());
+
+        let filled = svg_with_style(SvgStyle::Filled);
+        assert_eq!(attributes(&filled), [None, None, None]);
+        let outline = svg_with_style(SvgStyle::Outline);
+        assert_eq!(attributes(&outline), [Some("none".into()), Some("black".into()), Some("10".into())]);
+        let filled_outline = svg_with_style(SvgStyle::FilledOutline);
+        assert_eq!(attributes(&filled_outline), [Some("black".into()), Some("black".into()), Some("10".into())]);
+    }
+
+    #[test]
+    fn fill_rule_is_emitted() {
+        let svg_with_rule = |fill_rule| {
+            let options = SvgOptions { fill_rule, ..SvgOptions::default() };
+            assemble_svg(Point { x: 1, y: 1 }, CanvasConfig::default(), &[], &options)
+        };
+        assert_eq!(root_attribute(&svg_with_rule(FillRule::EvenOdd), "fill-rule"), "evenodd");
+        assert_eq!(root_attribute(&svg_with_rule(FillRule::NonZero), "fill-rule"), "nonzero");
+    }
+
+    #[test]
+    fn coordinates_lose_trailing_zeroes() {
+        assert_eq!(format_coordinate(100.0, 0), "100");
+        assert_eq!(format_coordinate(1.5, 3), "1.5");
+        assert_eq!(format_coordinate(2.0, 3), "2");
+        assert_eq!(format_coordinate(0.12345, 2), "0.12");
+        assert_eq!(format_coordinate(-0.001, 2), "0");
+    }
+
+    #[test]
+    fn coordinates_are_rounded_to_the_precision() {
+        let paths = [ClosedPath::from_coords(&[(130, 70), (333, 70), (333, 260)])];
+        let options = |precision| SvgOptions { precision, cell_units: true, ..SvgOptions::default() };
+        let canvas = CanvasConfig::default();
+        let rounded = assemble_svg(Point { x: 4, y: 4 }, canvas, &paths, &options(0));
+        assert_eq!(path_definitions(&rounded), vec!["M 1 1 L 3 1 L 3 3 z"]);
+        let precise = assemble_svg(Point { x: 4, y: 4 }, canvas, &paths, &options(2));
+        assert_eq!(path_definitions(&precise), vec!["M 1.3 0.7 L 3.33 0.7 L 3.33 2.6 z"]);
+    }
+
+    #[test]
+    fn cell_units_divide_by_the_cell_size() {
+        let paths = [ClosedPath::from_coords(&[(200, 100), (300, 100), (300, 200)])];
+        let options = SvgOptions { cell_units: true, ..SvgOptions::default() };
+        let canvas = CanvasConfig::default();
+        let svg = assemble_svg(Point { x: 4, y: 3 }, canvas, &paths, &options);
+        assert_eq!(path_definitions(&svg), vec!["M 2 1 L 3 1 L 3 2 z"]);
+        assert_eq!(root_attribute(&svg, "width"), "400");
+        assert_eq!(root_attribute(&svg, "viewBox"), "0 0 4 3");
+        assert_eq!(parse_svg(&svg, canvas), Ok((Point { x: 4, y: 3 }, paths.to_vec())));
+    }
+
+    #[test]
+    fn colors_are_parsed_back() {
+        assert_eq!(css_color(0x0080FF), "#ff8000");
+        assert_eq!(parse_css_color("#ff8000"), Some(0x0080FF));
+        assert_eq!(parse_css_color("black"), Some(DEFAULT_COLOR));
+        assert_eq!(parse_css_color("#f80"), None);
+        assert_eq!(parse_css_color("red"), None);
+    }
+
+    #[test]
+    fn empty_drawing_has_no_paths() {
+        let svg = assemble_svg(Point { x: 3, y: 2 }, CanvasConfig::default(), &[], &SvgOptions::default());
+        assert_eq!(root_attribute(&svg, "width"), "300");
+        assert_eq!(root_attribute(&svg, "height"), "200");
+        assert_eq!(path_definitions(&svg), Vec::<String>::new());
+        assert_eq!(parse_svg(&svg, CanvasConfig::default()), Ok((Point { x: 3, y: 2 }, Vec::new())));
+    }
+
+    #[test]
+    fn triangle_is_exported() {
+        let paths = [ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100)])];
+        let svg = assemble_svg(Point { x: 2, y: 2 }, CanvasConfig::default(), &paths, &SvgOptions::default());
+        assert_eq!(path_definitions(&svg), vec!["M 0 0 L 100 0 L 100 100 z"]);
+    }
+
+    #[test]
+    fn each_path_becomes_its_own_element() {
+        let paths = [
+            ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100)]),
+            ClosedPath::default(),
+            ClosedPath::from_coords(&[(200, 200), (300, 200), (300, 300), (200, 300)]),
+        ];
+        let svg = assemble_svg(Point { x: 4, y: 4 }, CanvasConfig::default(), &paths, &SvgOptions::default());
+        assert_eq!(path_definitions(&svg), vec![
+            "M 0 0 L 100 0 L 100 100 z",
+            "M 200 200 L 300 200 L 300 300 L 200 300 z",
+        ]);
+    }
+
+    #[test]
+    fn path_data_is_split_into_tokens() {
+        use PathToken::{Command, Number};
+        assert_eq!(tokenize_path_data("M 0,0 L-1.5e2-.5 10 20z"), vec![
+            Command('M'), Number(0.0), Number(0.0),
+            Command('L'), Number(-150.0), Number(-0.5), Number(10.0), Number(20.0),
+            Command('z'),
+        ]);
+        assert_eq!(tokenize_path_data("M1.5.5"), vec![Command('M'), Number(1.5), Number(0.5)]);
+        assert_eq!(tokenize_path_data(""), Vec::new());
+    }
+
+    #[test]
+    fn path_definitions_are_parsed() {
+        let to_point = |x: f64, y: f64| Point { x: x as i32, y: y as i32 };
+
+        let mut holed = ClosedPath::from_coords(&[(0, 0), (40, 0), (40, 40), (10, 10), (10, 30), (30, 30)]);
+        holed.subpath_starts = vec![3];
+        let parsed = parse_path_definition("M 0 0 L 40 0 L 40 40 z M 10 10 L 10 30 L 30 30 z", to_point);
+        assert_eq!(parsed, Ok(holed));
+
+        let mut curved = ClosedPath::from_coords(&[(0, 0), (10, 20), (30, 0)]);
+        curved.closed = false;
+        curved.control_points = vec![1];
+        assert_eq!(parse_path_definition("M 0 0 Q 10 20 30 0", to_point), Ok(curved));
+
+        // the end of a closing curve is the start of the subpath
+        let mut closing_curve = ClosedPath::from_coords(&[(0, 0), (20, 0), (20, 20)]);
+        closing_curve.control_points = vec![2];
+        assert_eq!(parse_path_definition("M 0 0 L 20 0 Q 20 20 0 0 z", to_point), Ok(closing_curve));
+
+        assert_eq!(parse_path_definition("M 0 0 A 1 1 0 0 0 5 5", to_point), Err(ParseError::UnsupportedCommand('A')));
+        assert_eq!(parse_path_definition("M 0 0 L 5", to_point), Err(ParseError::InvalidCoordinates));
+        assert_eq!(parse_path_definition("L 5 5", to_point), Err(ParseError::InvalidCoordinates));
+        assert_eq!(parse_path_definition("", to_point), Err(ParseError::InvalidCoordinates));
+    }
+
+    #[test]
+    fn combined_path_data_is_separated_by_single_spaces() {
+        let canvas = CanvasConfig::default();
+        let options = SvgOptions::default();
+        let point = ClosedPath::from_coords(&[(10, 20)]);
+        let mut line = ClosedPath::from_coords(&[(0, 0), (100, 0)]);
+        line.closed = false;
+
+        assert_eq!(build_path_data(canvas, std::slice::from_ref(&point), &options), "M 10 20 z");
+        assert_eq!(build_path_data(canvas, std::slice::from_ref(&line), &options), "M 0 0 L 100 0");
+        assert_eq!(
+            build_path_data(canvas, &[point, ClosedPath::default(), line], &options),
+            "M 10 20 z M 0 0 L 100 0",
+        );
+        assert_eq!(build_path_data(canvas, &[], &options), "");
+    }
+
+    #[test]
+    fn donut_is_one_path_with_two_subpaths() {
+        let mut donut = ClosedPath::from_coords(&[
+            (0, 0), (400, 0), (400, 400), (0, 400), (100, 100), (100, 300), (300, 300), (300, 100),
+        ]);
+        donut.subpath_starts = vec![4];
+        let svg = assemble_svg(Point { x: 4, y: 4 }, CanvasConfig::default(), &[donut], &SvgOptions::default());
+        assert_eq!(path_definitions(&svg), vec![
+            "M 0 0 L 400 0 L 400 400 L 0 400 z M 100 100 L 100 300 L 300 300 L 300 100 z",
+        ]);
+    }
+
+    #[test]
+    fn shape_ids_are_unique_and_stable() {
+        let labelled = |label: Option<&str>| ClosedPath {
+            label: label.map(String::from),
+            ..ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100)])
+        };
+        let paths = [
+            ClosedPath::default(),
+            labelled(Some("wing")),
+            labelled(Some("wing")),
+            labelled(None),
+            labelled(Some("1st shape")),
+        ];
+        let options = SvgOptions { path_ids: true, ..SvgOptions::default() };
+        let canvas = CanvasConfig::default();
+        let svg = assemble_svg(Point { x: 2, y: 2 }, canvas, &paths, &options);
+        let ids: Vec<Option<String>> = shape_attributes(&svg, "id").into_iter()
+            .map(|(_name, id)| id)
+            .collect();
+        assert_eq!(ids, ["wing", "wing-2", "path-3", "_1st_shape"].map(|id| Some(String::from(id))));
+        assert_eq!(assemble_svg(Point { x: 2, y: 2 }, canvas, &paths, &options), svg);
+
+        // a subset keeps the ids of the full document
+        let subset = assemble_svg_subset(Point { x: 2, y: 2 }, canvas, &paths, &BTreeSet::from([2]), &options);
+        assert_eq!(shape_attributes(&subset, "id"), vec![(String::from("path"), Some(String::from("wing-2")))]);
+    }
+
+    #[test]
+    fn background_is_the_first_child() {
+        let paths = [ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100)])];
+        let canvas = CanvasConfig::default();
+        let with_background = SvgOptions { background: Some(0xFFFFFF), ..SvgOptions::default() };
+        let svg = assemble_svg(Point { x: 2, y: 2 }, canvas, &paths, &with_background);
+        assert_eq!(shape_attributes(&svg, "fill"), vec![
+            (String::from("rect"), Some(String::from("#ffffff"))),
+            (String::from("path"), None),
+        ]);
+        assert_eq!(shape_attributes(&svg, "width")[0].1.as_deref(), Some("200"));
+        // the background is not part of the drawing
+        assert_eq!(parse_svg(&svg, canvas), Ok((Point { x: 2, y: 2 }, paths.to_vec())));
+
+        let svg = assemble_svg(Point { x: 2, y: 2 }, canvas, &paths, &SvgOptions::default());
+        assert_eq!(shape_attributes(&svg, "fill"), vec![(String::from("path"), None)]);
+    }
+
+    #[test]
+    fn path_data_matches_the_exported_shapes() {
+        let mut open = ClosedPath::from_coords(&[(0, 0), (150, 50)]);
+        open.closed = false;
+        let paths = [ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100)]), open];
+        let canvas = CanvasConfig::default();
+        let options = SvgOptions { cell_units: true, precision: 1, ..SvgOptions::default() };
+        let svg = assemble_svg(Point { x: 2, y: 2 }, canvas, &paths, &options);
+        assert_eq!(build_path_data(canvas, &paths, &options), path_definitions(&svg).join(" "));
+        assert_eq!(build_path_data(canvas, &paths, &options), "M 0 0 L 1 0 L 1 1 z M 0 0 L 1.5 0.5");
+
+        // painted cells become paths too
+        let cell = ClosedPath::new_cell(Point { x: 100, y: 0 }, canvas, 0x0000FF);
+        assert_eq!(build_path_data(canvas, &[cell], &SvgOptions::default()), "M 100 0 L 200 0 L 200 100 L 100 100 z");
+    }
+
+    #[test]
+    fn two_paths_are_joined_by_a_single_space() {
+        let paths = [
+            ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100)]),
+            ClosedPath::from_coords(&[(200, 200), (300, 200), (300, 300)]),
+        ];
+        let data = build_path_data(CanvasConfig::default(), &paths, &SvgOptions::default());
+        assert_eq!(data, "M 0 0 L 100 0 L 100 100 z M 200 200 L 300 200 L 300 300 z");
+        assert!(!data.contains("  "));
+        assert_eq!(data.trim(), data);
+    }
+
+    #[test]
+    fn unknown_documents_are_rejected() {
+        let canvas = CanvasConfig::default();
+        assert!(matches!(parse_svg("<svg", canvas), Err(ParseError::Xml(_))));
+        assert_eq!(parse_svg("<html width=\"1\" height=\"1\"/>", canvas), Err(ParseError::NotSvg));
+        assert_eq!(parse_svg("<svg xmlns=\"http://www.w3.org/2000/svg\"/>", canvas), Err(ParseError::NotSvg));
+    }
+
+    #[test]
+    fn open_path_is_not_closed() {
+        let mut open = ClosedPath::from_coords(&[(0, 0), (100, 0), (100, 100)]);
+        open.closed = false;
+        let svg = assemble_svg(Point { x: 2, y: 2 }, CanvasConfig::default(), &[open], &SvgOptions::default());
+        assert_eq!(path_definitions(&svg), vec!["M 0 0 L 100 0 L 100 100"]);
+    }
+
+    #[test]
+    fn view_box_includes_points_outside_the_grid() {
+        let paths = [ClosedPath::from_coords(&[(-100, 50), (300, 50), (300, 500)])];
+        let svg = assemble_svg(Point { x: 2, y: 2 }, CanvasConfig::default(), &paths, &SvgOptions::default());
+        assert_eq!(root_attribute(&svg, "width"), "200");
+        assert_eq!(root_attribute(&svg, "height"), "200");
+        assert_eq!(root_attribute(&svg, "viewBox"), "-100 0 400 500");
+    }
+
+    #[test]
+    fn huge_grid_is_exported_with_bounded_dimensions() {
+        let paths = [ClosedPath::from_coords(&[(i32::MIN, 0), (0, 0), (0, 100)])];
+        let svg = assemble_svg(Point { x: i32::MAX, y: 3 }, CanvasConfig::default(), &paths, &SvgOptions::default());
+        assert_eq!(root_attribute(&svg, "width"), MAX_DIMENSION.to_string());
+        assert_eq!(root_attribute(&svg, "height"), "300");
+        assert_eq!(root_attribute(&svg, "viewBox"), "-2147483648 0 4294967295 300");
+    }
+
+    #[test]
+    fn empty_grid_is_exported_as_one_cell() {
+        let svg = assemble_svg(Point { x: 0, y: 0 }, CanvasConfig::default(), &[], &SvgOptions::default());
+        assert_eq!(root_attribute(&svg, "width"), "100");
+        assert_eq!(root_attribute(&svg, "height"), "100");
+        assert_eq!(root_attribute(&svg, "viewBox"), "0 0 100 100");
+    }
+
+    #[test]
+    fn mirrored_cells_are_exported_as_rectangles() {
+        use crate::transform::MirrorAxis;
+
+        let canvas = CanvasConfig::default();
+        let grid = Point { x: 4, y: 4 };
+        let rect_attributes = |svg: &str| ["x", "y", "width", "height"]
+            .map(|name| shape_attributes(svg, name)[0].1.clone().unwrap());
+        for (axis, expected) in [(MirrorAxis::Vertical, ["300", "0"]), (MirrorAxis::Horizontal, ["0", "300"])] {
+            let mut cell = ClosedPath::new_cell(Point { x: 0, y: 0 }, canvas, DEFAULT_COLOR);
+            cell.mirror(axis, grid, canvas);
+            let svg = assemble_svg(grid, canvas, &[cell], &SvgOptions::default());
+            assert_eq!(rect_attributes(&svg), [expected[0], expected[1], "100", "100"]);
+        }
+    }
+
+    #[test]
+    fn closed_flag_and_color_are_parsed_back() {
+        let mut closed = ClosedPath::from_coords(&[(0, 0), (200, 0), (200, 200)]);
+        closed.color = 0x0000FF;
+        let mut open = ClosedPath::from_coords(&[(0, 0), (100, 300), (300, 300)]);
+        open.closed = false;
+        open.color = 0x00C000;
+
+        let canvas = CanvasConfig::default();
+        let svg = assemble_svg(Point { x: 4, y: 4 }, canvas, &[closed, open], &SvgOptions::default());
+        let (_grid, paths) = parse_svg(&svg, canvas).unwrap();
+        let restored: Vec<(bool, u32)> = paths.iter()
+            .map(|path| (path.closed, path.color))
+            .collect();
+        assert_eq!(restored, vec![(true, 0x0000FF), (false, 0x00C000)]);
+    }
+
+    #[test]
+    fn saved_paths_are_parsed_back() {
+        let mut smooth = ClosedPath::from_coords(&[(100, 100), (300, 100), (200, 300)]);
+        smooth.smooth = true;
+        smooth.tension_percent = 25;
+        let mut smooth_open = ClosedPath::from_coords(&[(0, 0), (100, 200), (300, 200), (400, 0)]);
+        smooth_open.smooth = true;
+        smooth_open.closed = false;
+        let mut holed = ClosedPath::from_coords(&[
+            (0, 0), (400, 0), (400, 400), (0, 400), (100, 100), (100, 300), (300, 300), (300, 100),
+        ]);
+        holed.subpath_starts = vec![4];
+        let paths = vec![ClosedPath::from_coords(&[(0, 0), (200, 0), (200, 200)]), smooth, smooth_open, holed];
+
+        let grid = Point { x: 4, y: 4 };
+        let canvas = CanvasConfig::default();
+        let svg = assemble_svg(grid, canvas, &paths, &SvgOptions::default());
+        assert_eq!(parse_svg(&svg, canvas), Ok((grid, paths)));
+    }
+}